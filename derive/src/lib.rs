@@ -0,0 +1,190 @@
+//! Derive macros for the [telegram-bot](https://docs.rs/telegram-bot/) crate.
+//!
+//! Use them through the re-exports in `telegram-bot`, this crate is not meant to be used directly.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Error, Fields, Lit, Meta, NestedMeta};
+
+/// Implements `BotCommands` for an enum, see the documentation of the trait
+/// in `telegram-bot` for the supported attributes.
+#[proc_macro_derive(BotCommands, attributes(command))]
+pub fn derive_bot_commands(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match bot_commands(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+struct VariantAttributes {
+    rename: Option<String>,
+    description: Option<String>,
+    rest: bool,
+}
+
+fn bot_commands(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let data = match input.data {
+        Data::Enum(ref data) => data,
+        _ => {
+            return Err(Error::new_spanned(
+                input,
+                "BotCommands can be derived only for enums",
+            ))
+        }
+    };
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut arms = Vec::new();
+    let mut commands = Vec::new();
+    for variant in &data.variants {
+        let attributes = variant_attributes(&variant.attrs)?;
+        let name = attributes
+            .rename
+            .unwrap_or_else(|| snake_case(&variant.ident.to_string()));
+        let description = attributes
+            .description
+            .or_else(|| doc_comment(&variant.attrs))
+            .unwrap_or_default();
+        let variant_ident = &variant.ident;
+
+        let constructor = if attributes.rest {
+            let field = match variant.fields {
+                Fields::Named(ref fields) if fields.named.len() == 1 => {
+                    let field = &fields.named[0].ident;
+                    quote!({ #field: command.parse_rest()? })
+                }
+                Fields::Unnamed(ref fields) if fields.unnamed.len() == 1 => {
+                    quote!((command.parse_rest()?))
+                }
+                _ => {
+                    return Err(Error::new_spanned(
+                        variant,
+                        "`rest` requires a variant with exactly one field",
+                    ))
+                }
+            };
+            quote!(#ident::#variant_ident #field)
+        } else {
+            let count = variant.fields.len();
+            let field = match variant.fields {
+                Fields::Named(ref fields) => {
+                    let fields = fields.named.iter().enumerate().map(|(index, field)| {
+                        let field = &field.ident;
+                        quote!(#field: command.arg(#index)?)
+                    });
+                    quote!({ #(#fields),* })
+                }
+                Fields::Unnamed(ref fields) => {
+                    let fields =
+                        (0..fields.unnamed.len()).map(|index| quote!(command.arg(#index)?));
+                    quote!((#(#fields),*))
+                }
+                Fields::Unit => quote!(),
+            };
+            quote!({
+                command.expect_args(#count)?;
+                #ident::#variant_ident #field
+            })
+        };
+
+        arms.push(quote!(#name => Ok(#constructor),));
+        commands.push(quote!((#name, #description)));
+    }
+
+    Ok(quote! {
+        impl #impl_generics ::telegram_bot::BotCommands for #ident #ty_generics #where_clause {
+            fn from_command(
+                command: &::telegram_bot::Command,
+            ) -> ::std::result::Result<Self, ::telegram_bot::CommandError> {
+                match command.name.as_str() {
+                    #(#arms)*
+                    _ => Err(::telegram_bot::CommandError::UnknownCommand(command.name.clone())),
+                }
+            }
+
+            fn commands() -> ::std::vec::Vec<(&'static str, &'static str)> {
+                vec![#(#commands),*]
+            }
+        }
+    })
+}
+
+fn variant_attributes(attrs: &[Attribute]) -> Result<VariantAttributes, Error> {
+    let mut attributes = VariantAttributes {
+        rename: None,
+        description: None,
+        rest: false,
+    };
+
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("command")) {
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => return Err(Error::new_spanned(meta, "expected #[command(...)]")),
+        };
+
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::Path(ref path)) if path.is_ident("rest") => {
+                    attributes.rest = true;
+                }
+                NestedMeta::Meta(Meta::NameValue(ref pair)) => {
+                    let value = match pair.lit {
+                        Lit::Str(ref value) => value.value(),
+                        ref lit => return Err(Error::new_spanned(lit, "expected a string")),
+                    };
+                    if pair.path.is_ident("rename") {
+                        attributes.rename = Some(value);
+                    } else if pair.path.is_ident("description") {
+                        attributes.description = Some(value);
+                    } else {
+                        return Err(Error::new_spanned(&pair.path, "unknown attribute"));
+                    }
+                }
+                nested => return Err(Error::new_spanned(nested, "unknown attribute")),
+            }
+        }
+    }
+
+    Ok(attributes)
+}
+
+fn doc_comment(attrs: &[Attribute]) -> Option<String> {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("doc"))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(Meta::NameValue(pair)) => match pair.lit {
+                Lit::Str(value) => Some(value.value().trim().to_string()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join(" "))
+    }
+}
+
+fn snake_case(ident: &str) -> String {
+    let mut name = String::new();
+    for (index, ch) in ident.chars().enumerate() {
+        if ch.is_uppercase() {
+            if index > 0 {
+                name.push('_');
+            }
+            name.extend(ch.to_lowercase());
+        } else {
+            name.push(ch);
+        }
+    }
+    name
+}
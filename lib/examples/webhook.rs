@@ -0,0 +1,20 @@
+use futures::StreamExt;
+use telegram_bot::*;
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    // Telegram should be configured to deliver updates to `https://<host>/telegram`,
+    // for example by a TLS-terminating reverse proxy in front of this server.
+    let mut stream = WebhookStream::new(([0, 0, 0, 0], 8080));
+    stream.path("/telegram");
+
+    while let Some(update) = stream.next().await {
+        let update = update?;
+        if let UpdateKind::Message(message) = update.kind {
+            if let MessageKind::Text { ref data, .. } = message.kind {
+                println!("<{}>: {}", &message.from.first_name, data);
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,17 +1,27 @@
+use std::path::Path;
 use std::sync::{
     atomic::{AtomicUsize, Ordering},
-    Arc,
+    Arc, Mutex,
 };
+use std::task::{Context, Poll, Waker};
 use std::time::Duration;
 
-use futures::{Future, FutureExt};
-use tokio::time::timeout;
+use bytes::Bytes;
+use futures::{future, stream, Future, FutureExt, Stream, StreamExt, TryStreamExt};
+use tokio::io::AsyncWriteExt;
+use tokio::task::JoinHandle;
+use tokio::time::{delay_for, timeout};
 use tracing_futures::Instrument;
 
-use telegram_bot_raw::{HttpRequest, Request, ResponseType};
+use telegram_bot_raw::{
+    telegram_api_url, ApiUrl, ChatId, File, HttpRequest, MessageOrChannelPost, Request,
+    ResponseType, RichText, SendMessage, ToChatRef, ToMessageId, ToSourceChat,
+    MESSAGE_TEXT_MAX_LENGTH,
+};
 
 use crate::connector::{default_connector, Connector};
 use crate::errors::{Error, ErrorKind};
+use crate::retry::RetryPolicy;
 use crate::stream::UpdatesStream;
 
 /// Main type for sending requests to the Telegram bot API.
@@ -20,10 +30,111 @@ pub struct Api(Arc<ApiInner>);
 
 struct ApiInner {
     token: String,
+    url: ApiUrl,
     connector: Box<dyn Connector>,
+    retry_policy: Option<RetryPolicy>,
+    spawned: Arc<SpawnedRequests>,
     next_request_id: AtomicUsize,
 }
 
+/// Tracker of requests executed in background tasks.
+#[derive(Default)]
+struct SpawnedRequests {
+    state: Mutex<(usize, Vec<Waker>)>,
+}
+
+struct SpawnedGuard(Arc<SpawnedRequests>);
+
+impl SpawnedRequests {
+    fn track(self: &Arc<Self>) -> SpawnedGuard {
+        self.state.lock().unwrap().0 += 1;
+        SpawnedGuard(self.clone())
+    }
+
+    fn in_flight(&self) -> usize {
+        self.state.lock().unwrap().0
+    }
+
+    fn poll_idle(&self, cx: &mut Context) -> Poll<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.0 == 0 {
+            return Poll::Ready(());
+        }
+        state.1.push(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for SpawnedGuard {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock().unwrap();
+        state.0 -= 1;
+        if state.0 == 0 {
+            for waker in state.1.drain(..) {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// Builder of the `Api` instance with non-default settings.
+pub struct ApiBuilder {
+    token: String,
+    base_url: Option<String>,
+    file_base_url: Option<String>,
+    connector: Option<Box<dyn Connector>>,
+    retry_policy: Option<RetryPolicy>,
+}
+
+impl ApiBuilder {
+    /// Use custom connector instead of the default one.
+    pub fn connector(mut self, connector: Box<dyn Connector>) -> Self {
+        self.connector = Some(connector);
+        self
+    }
+
+    /// Send requests to the Bot API server with `base_url`, e.g. a local Bot API server or a fake
+    /// server for testing. Files are downloaded from `<base_url>file/` unless `file_base_url` is set.
+    ///
+    /// By default the URL is obtained from `TELEGRAM_API_URL` environment variable
+    /// or `https://api.telegram.org/` if the variable is not set.
+    pub fn base_url<T: AsRef<str>>(mut self, base_url: T) -> Self {
+        self.base_url = Some(base_url.as_ref().to_string());
+        self
+    }
+
+    /// Download files from the server with `file_base_url`.
+    pub fn file_base_url<T: AsRef<str>>(mut self, file_base_url: T) -> Self {
+        self.file_base_url = Some(file_base_url.as_ref().to_string());
+        self
+    }
+
+    /// Retry requests rejected by Telegram according to the `policy`.
+    /// By default requests are not retried.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Create a new `Api` instance.
+    pub fn build(self) -> Api {
+        let base_url = self.base_url.unwrap_or_else(telegram_api_url);
+        let url = match self.file_base_url {
+            Some(file_base_url) => ApiUrl::with_file_base_url(base_url, file_base_url),
+            None => ApiUrl::new(base_url),
+        };
+
+        Api(Arc::new(ApiInner {
+            token: self.token,
+            url,
+            connector: self.connector.unwrap_or_else(default_connector),
+            retry_policy: self.retry_policy,
+            spawned: Default::default(),
+            next_request_id: AtomicUsize::new(0),
+        }))
+    }
+}
+
 impl Api {
     /// Create a new `Api` instance.
     ///
@@ -45,11 +156,31 @@ impl Api {
 
     /// Create a new `Api` instance wtih custom connector.
     pub fn with_connector<T: AsRef<str>>(token: T, connector: Box<dyn Connector>) -> Self {
-        Api(Arc::new(ApiInner {
+        Self::builder(token).connector(connector).build()
+    }
+
+    /// Create a builder of the `Api` instance with non-default settings.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use telegram_bot::{Api, RetryPolicy};
+    ///
+    /// # fn main() {
+    /// # let telegram_token = "token";
+    /// let api = Api::builder(telegram_token)
+    ///     .retry_policy(RetryPolicy::new())
+    ///     .build();
+    /// # }
+    /// ```
+    pub fn builder<T: AsRef<str>>(token: T) -> ApiBuilder {
+        ApiBuilder {
             token: token.as_ref().to_string(),
-            connector,
-            next_request_id: AtomicUsize::new(0),
-        }))
+            base_url: None,
+            file_base_url: None,
+            connector: None,
+            retry_policy: None,
+        }
     }
 
     /// Create a stream which produces updates from the Telegram server.
@@ -92,12 +223,94 @@ impl Api {
     /// # }
     /// ```
     pub fn spawn<Req: Request>(&self, request: Req) {
+        self.spawn_with_error_handler(request, |_| ());
+    }
+
+    /// Send a request to the Telegram server in a background task,
+    /// the response can be obtained from the returned `JoinHandle`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use telegram_bot::{Api, ChatId, prelude::*};
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// # let telegram_token = "token";
+    /// # let api = Api::new(telegram_token);
+    /// # if false {
+    /// let chat = ChatId::new(61031);
+    /// let handle = api.spawn_with_handle(chat.text("Message"));
+    /// println!("{:?}", handle.await);
+    /// # }
+    /// # }
+    /// ```
+    pub fn spawn_with_handle<Req: Request>(
+        &self,
+        request: Req,
+    ) -> JoinHandle<Result<<Req::Response as ResponseType>::Type, Error>>
+    where
+        <Req::Response as ResponseType>::Type: Send + 'static,
+    {
         let api = self.clone();
-        if let Ok(request) = request.serialize() {
-            tokio::spawn(async move {
-                let _ = api.send_http_request::<Req::Response>(request).await;
-            });
-        }
+        let request = request.serialize();
+        let guard = self.0.spawned.track();
+        tokio::spawn(async move {
+            let _guard = guard;
+            api.send_http_request::<Req::Response>(request.map_err(ErrorKind::from)?)
+                .await
+        })
+    }
+
+    /// Send a request to the Telegram server and do not wait for a response,
+    /// errors are reported to the `handler`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use telegram_bot::{Api, ChatId, prelude::*};
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// # let telegram_token = "token";
+    /// # let api = Api::new(telegram_token);
+    /// # if false {
+    /// let chat = ChatId::new(61031);
+    /// api.spawn_with_error_handler(chat.text("Message"), |error| {
+    ///     eprintln!("unable to send message: {}", error)
+    /// });
+    /// # }
+    /// # }
+    /// ```
+    pub fn spawn_with_error_handler<Req, F>(&self, request: Req, handler: F)
+    where
+        Req: Request,
+        F: FnOnce(Error) + Send + 'static,
+    {
+        let api = self.clone();
+        let request = request.serialize();
+        let guard = self.0.spawned.track();
+        tokio::spawn(async move {
+            let _guard = guard;
+            let result = match request {
+                Ok(request) => api
+                    .send_http_request::<Req::Response>(request)
+                    .await
+                    .map(|_| ()),
+                Err(error) => Err(ErrorKind::from(error).into()),
+            };
+            if let Err(error) = result {
+                handler(error);
+            }
+        });
+    }
+
+    /// Wait for requests started with [`spawn`](#method.spawn) and its variants at most
+    /// for `deadline`, returns the number of requests which are still in flight.
+    pub async fn wait_spawned(&self, deadline: Duration) -> usize {
+        let spawned = &self.0.spawned;
+        let _ = timeout(deadline, future::poll_fn(|cx| spawned.poll_idle(cx))).await;
+        spawned.in_flight()
     }
 
     /// Send a request to the Telegram server and wait for a response, timing out after `duration`.
@@ -170,7 +383,192 @@ impl Api {
         }
     }
 
+    /// Send the `text` as replies to the `message`, split into chunks which fit
+    /// the maximum length of a message text. Chunks are sent in order, sending stops
+    /// at the first error. See [`RichText::split`] for how the text is split.
+    ///
+    /// [`RichText::split`]: struct.RichText.html#method.split
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use telegram_bot::{Api, Message};
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), telegram_bot::Error> {
+    /// # let telegram_token = "token";
+    /// # let api = Api::new(telegram_token);
+    /// # if false {
+    /// # let message: Message = serde_json::from_str(r#"{
+    /// #     "message_id": 1, "date": 0, "text": "/log",
+    /// #     "from": {"id": 1, "is_bot": false, "first_name": "John"},
+    /// #     "chat": {"id": 1, "type": "private", "first_name": "John"}
+    /// # }"#).unwrap();
+    /// let log = "line\n".repeat(2000);
+    /// let replies = api.send_split_reply(&message, log).await?;
+    /// assert_eq!(replies.len(), 3);
+    /// # }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send_split_reply<M, T>(
+        &self,
+        message: &M,
+        text: T,
+    ) -> impl Future<Output = Result<Vec<MessageOrChannelPost>, Error>> + Send
+    where
+        M: ToMessageId + ToSourceChat,
+        T: Into<RichText>,
+    {
+        let api = self.clone();
+        let chat = message.to_source_chat();
+        let message_id = message.to_message_id();
+        let chunks = text.into().split(MESSAGE_TEXT_MAX_LENGTH);
+        async move {
+            let mut messages = Vec::with_capacity(chunks.len());
+            for chunk in chunks {
+                let mut request = SendMessage::new(chat, "");
+                request.rich_text(&chunk).reply_to(message_id);
+                messages.push(api.send(request).await?);
+            }
+            Ok(messages)
+        }
+    }
+
+    /// Obtains URL of the file for downloading, `file` is obtained from the `GetFile` request.
+    pub fn file_url(&self, file: &File) -> Option<String> {
+        file.get_url_with(&self.0.url, &self.0.token)
+    }
+
+    /// Download contents of the file, `file` is obtained from the `GetFile` request.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use telegram_bot::{Api, PhotoSize, prelude::*};
+    /// use futures::TryStreamExt;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), telegram_bot::Error> {
+    /// # let telegram_token = "token";
+    /// # let api = Api::new(telegram_token);
+    /// # if false {
+    /// # let photo: PhotoSize = serde_json::from_str(
+    /// #     r#"{"file_id": "photo", "width": 1, "height": 1}"#
+    /// # ).unwrap();
+    /// let file = api.send(photo.get_file()).await?;
+    /// let mut contents = api.download_file(&file);
+    /// while let Some(chunk) = contents.try_next().await? {
+    ///     println!("received {} bytes", chunk.len());
+    /// }
+    /// # }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn download_file(
+        &self,
+        file: &File,
+    ) -> impl Stream<Item = Result<Bytes, Error>> + Send + Unpin {
+        match file.file_path {
+            Some(ref file_path) => {
+                tracing::trace!(file_path = %file_path, "downloading file");
+                self.0
+                    .connector
+                    .download(&self.0.url, &self.0.token, file_path)
+            }
+            None => stream::once(future::err(ErrorKind::MissingFilePath.into())).boxed(),
+        }
+    }
+
+    /// Download contents of the file and write them to `path`.
+    /// Partially written file is removed if the download fails.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use telegram_bot::{Api, PhotoSize, prelude::*};
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), telegram_bot::Error> {
+    /// # let telegram_token = "token";
+    /// # let api = Api::new(telegram_token);
+    /// # if false {
+    /// # let photo: PhotoSize = serde_json::from_str(
+    /// #     r#"{"file_id": "photo", "width": 1, "height": 1}"#
+    /// # ).unwrap();
+    /// let file = api.send(photo.get_file()).await?;
+    /// api.download_file_to(&file, "photo.jpg").await?;
+    /// # }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn download_file_to<P: AsRef<Path>>(
+        &self,
+        file: &File,
+        path: P,
+    ) -> Result<(), Error> {
+        let path = path.as_ref();
+        let mut contents = self.download_file(file);
+        let mut output = tokio::fs::File::create(path)
+            .await
+            .map_err(ErrorKind::from)?;
+
+        let result = async {
+            while let Some(chunk) = contents.try_next().await? {
+                output.write_all(&chunk).await.map_err(ErrorKind::from)?;
+            }
+            output.flush().await.map_err(ErrorKind::from)?;
+            Ok::<(), Error>(())
+        }
+        .await;
+
+        if result.is_err() {
+            drop(output);
+            let _ = tokio::fs::remove_file(path).await;
+        }
+        result
+    }
+
     async fn send_http_request<Resp: ResponseType>(
+        &self,
+        mut request: HttpRequest,
+    ) -> Result<Resp::Type, Error> {
+        let policy = match self.0.retry_policy {
+            Some(ref policy) => policy,
+            None => return self.send_http_request_once::<Resp>(request).await,
+        };
+
+        let mut attempt = 1;
+        loop {
+            let error = match self.send_http_request_once::<Resp>(request.clone()).await {
+                Ok(response) => return Ok(response),
+                Err(error) => error,
+            };
+            let delay = match policy.delay(attempt, &error) {
+                Some(delay) => delay,
+                None => return Err(error),
+            };
+
+            if let Some(chat_id) = error.migrate_to_chat_id() {
+                let chat = ChatId::new(chat_id).to_chat_ref();
+                if !request.set_chat_id(chat) {
+                    return Err(error);
+                }
+            }
+
+            tracing::warn!(
+                name = %request.name(),
+                attempt = attempt,
+                delay = ?delay,
+                error = %error,
+                "retrying request"
+            );
+            delay_for(delay).await;
+            attempt += 1;
+        }
+    }
+
+    async fn send_http_request_once<Resp: ResponseType>(
         &self,
         request: HttpRequest,
     ) -> Result<Resp::Type, Error> {
@@ -178,8 +576,13 @@ impl Api {
         let span = tracing::trace_span!("send_http_request", request_id = request_id);
         async {
             tracing::trace!(name = %request.name(), body = %request.body, "sending request");
-            let http_response = self.0.connector.request(&self.0.token, request).await?;
+            let http_response = self
+                .0
+                .connector
+                .request(&self.0.url, &self.0.token, request)
+                .await?;
             tracing::trace!(
+                status = http_response.status,
                 response = %match http_response.body {
                     Some(ref vec) => match std::str::from_utf8(vec) {
                         Ok(str) => str,
@@ -203,3 +606,204 @@ impl Api {
         .await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::pin::Pin;
+
+    use serde_json::json;
+    use telegram_bot_raw::{GetMe, HttpResponse, Message, SendMessage};
+
+    use super::*;
+    use crate::connector::MockConnector;
+    use crate::test_util::temp_path;
+
+    fn file(file_path: Option<&str>) -> File {
+        File {
+            file_id: "1".to_string(),
+            file_size: None,
+            file_path: file_path.map(str::to_string),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_download_file() {
+        let mock = MockConnector::new();
+        mock.file("photos/1.jpg", &b"image"[..]);
+        let api = Api::with_connector("token", Box::new(mock));
+
+        let contents: Vec<Bytes> = api
+            .download_file(&file(Some("photos/1.jpg")))
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(contents.concat(), b"image");
+
+        let mut missing = api.download_file(&file(Some("photos/2.jpg")));
+        assert!(missing.try_next().await.is_err());
+        let error = api.download_file(&file(None)).try_next().await.unwrap_err();
+        assert_eq!(error.to_string(), "file path is not available");
+
+        let path = temp_path("download");
+        api.download_file_to(&file(Some("photos/1.jpg")), &path)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"image");
+        std::fs::remove_file(&path).unwrap();
+
+        let result = api
+            .download_file_to(&file(Some("photos/2.jpg")), &path)
+            .await;
+        assert!(result.is_err());
+        assert!(!path.exists());
+    }
+
+    fn api_error(body: serde_json::Value) -> HttpResponse {
+        HttpResponse {
+            status: 400,
+            headers: Vec::new(),
+            body: Some(body.to_string().into_bytes()),
+        }
+    }
+
+    fn message(chat_id: i64) -> HttpResponse {
+        MockConnector::ok(json!({
+            "message_id": 1,
+            "date": 0,
+            "from": {"id": 1, "is_bot": true, "first_name": "Bot"},
+            "chat": {"id": chat_id, "type": "supergroup", "title": "Group"},
+            "text": "Hello!",
+        }))
+    }
+
+    fn retrying_api(mock: &MockConnector) -> Api {
+        let mut policy = RetryPolicy::new();
+        policy.backoff(Duration::from_millis(10), Duration::from_millis(10));
+        Api::builder("token")
+            .connector(Box::new(mock.clone()))
+            .retry_policy(policy)
+            .build()
+    }
+
+    #[tokio::test]
+    async fn test_retry_after() {
+        let flood = || {
+            api_error(json!({
+                "ok": false,
+                "error_code": 429,
+                "description": "Too Many Requests: retry after 0",
+                "parameters": {"retry_after": 0},
+            }))
+        };
+        let mock = MockConnector::new();
+        mock.push(flood()).push(message(42));
+        let api = retrying_api(&mock);
+
+        api.send(SendMessage::new(ChatId::new(42), "Hello!"))
+            .await
+            .unwrap();
+        assert_eq!(mock.requests_to("sendMessage").len(), 2);
+
+        // The default policy gives up after 3 attempts.
+        mock.clear();
+        mock.push(flood()).push(flood()).push(flood());
+        let result = api.send(SendMessage::new(ChatId::new(42), "Hello!")).await;
+        assert!(result.unwrap_err().retry_after().is_some());
+        assert_eq!(mock.requests_to("sendMessage").len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_migrate_to_chat_id() {
+        let migrated = || {
+            api_error(json!({
+                "ok": false,
+                "error_code": 400,
+                "description": "Bad Request: group chat was upgraded to a supergroup chat",
+                "parameters": {"migrate_to_chat_id": -100123},
+            }))
+        };
+        let mock = MockConnector::new();
+        mock.push(migrated()).push(message(-100123));
+        let api = retrying_api(&mock);
+
+        api.send(SendMessage::new(ChatId::new(42), "Hello!"))
+            .await
+            .unwrap();
+        let requests = mock.requests_to("sendMessage");
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].params["chat_id"], json!(42));
+        assert_eq!(requests[1].params["chat_id"], json!(-100123));
+
+        // Requests without a chat can't be re-targeted.
+        mock.clear();
+        mock.push(migrated());
+        let result = api.send(GetMe).await;
+        assert_eq!(result.unwrap_err().migrate_to_chat_id(), Some(-100123));
+        assert_eq!(mock.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_send_split_reply() {
+        let command: Message = serde_json::from_value(json!({
+            "message_id": 7,
+            "date": 0,
+            "from": {"id": 43, "is_bot": false, "first_name": "John"},
+            "chat": {"id": 42, "type": "supergroup", "title": "Group"},
+            "text": "/log",
+        }))
+        .unwrap();
+        let log: String = (0..2000).map(|line| format!("line {}\n", line)).collect();
+        let chunks = RichText::from(log.as_str()).split(MESSAGE_TEXT_MAX_LENGTH);
+        assert_eq!(chunks.len(), 5);
+
+        let mock = MockConnector::new();
+        for _ in 0..chunks.len() {
+            mock.push(message(42));
+        }
+        let api = Api::with_connector("token", Box::new(mock.clone()));
+        let replies = api.send_split_reply(&command, log.as_str()).await.unwrap();
+        assert_eq!(replies.len(), chunks.len());
+
+        let requests = mock.requests_to("sendMessage");
+        assert_eq!(requests.len(), chunks.len());
+        for (request, chunk) in requests.iter().zip(&chunks) {
+            assert_eq!(request.params["chat_id"], json!(42));
+            assert_eq!(request.params["reply_to_message_id"], json!(7));
+            assert_eq!(request.params["text"], json!(chunk.to_plain()));
+        }
+
+        // Sending stops at the first error.
+        mock.clear();
+        mock.push(message(42))
+            .push(MockConnector::error(400, "Bad Request"))
+            .push(message(42));
+        let result = api.send_split_reply(&command, log.as_str()).await;
+        assert!(result.is_err());
+        let requests = mock.requests_to("sendMessage");
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].params["text"], json!(chunks[1].to_plain()));
+    }
+
+    #[derive(Debug)]
+    struct RequestOnlyConnector;
+
+    impl Connector for RequestOnlyConnector {
+        fn request(
+            &self,
+            _api_url: &ApiUrl,
+            _token: &str,
+            req: HttpRequest,
+        ) -> Pin<Box<dyn Future<Output = Result<HttpResponse, Error>> + Send>> {
+            let error = ErrorKind::UnexpectedRequest(req.name().to_string());
+            Box::pin(future::ready(Err(error.into())))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_download() {
+        let api = Api::with_connector("token", Box::new(RequestOnlyConnector));
+        let mut contents = api.download_file(&file(Some("photos/1.jpg")));
+        let error = contents.try_next().await.unwrap_err();
+        assert_eq!(error.to_string(), "connector doesn't support downloads");
+    }
+}
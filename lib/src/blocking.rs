@@ -0,0 +1,156 @@
+//! Blocking client for programs which don't use an async runtime.
+//!
+//! Requires the `blocking` feature.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::runtime::{Builder, Runtime};
+
+use telegram_bot_raw::{Request, ResponseType};
+
+use crate::connector::Connector;
+use crate::errors::{Error, ErrorKind};
+use crate::ApiBuilder;
+
+/// Blocking variant of the [`Api`](../struct.Api.html), every request blocks
+/// the current thread until a response is received.
+///
+/// Requests are executed on a private single-threaded runtime, so methods of this type
+/// must not be called from within an async runtime.
+///
+/// # Examples
+///
+/// ```rust
+/// use telegram_bot::blocking::Api;
+/// use telegram_bot::{ChatId, prelude::*};
+///
+/// # fn main() -> Result<(), telegram_bot::Error> {
+/// # let telegram_token = "token";
+/// let api = Api::new(telegram_token)?;
+/// # if false {
+/// api.send(ChatId::new(61031).text("Message"))?;
+/// # }
+/// # Ok(())
+/// # }
+/// ```
+pub struct Api {
+    api: crate::Api,
+    runtime: Mutex<Runtime>,
+}
+
+impl Api {
+    /// Create a new `Api` instance.
+    pub fn new<T: AsRef<str>>(token: T) -> Result<Self, Error> {
+        Self::from_builder(crate::Api::builder(token))
+    }
+
+    /// Create a new `Api` instance wtih custom connector.
+    pub fn with_connector<T: AsRef<str>>(
+        token: T,
+        connector: Box<dyn Connector>,
+    ) -> Result<Self, Error> {
+        Self::from_builder(crate::Api::builder(token).connector(connector))
+    }
+
+    /// Create a new `Api` instance with settings of the `builder`.
+    pub fn from_builder(builder: ApiBuilder) -> Result<Self, Error> {
+        let runtime = Builder::new()
+            .basic_scheduler()
+            .enable_all()
+            .build()
+            .map_err(ErrorKind::from)?;
+        // The default connector may need the runtime context.
+        let api = runtime.enter(|| builder.build());
+
+        Ok(Api {
+            api,
+            runtime: Mutex::new(runtime),
+        })
+    }
+
+    /// Send a request to the Telegram server and wait for a response.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use telegram_bot::blocking::Api;
+    /// # use telegram_bot::GetMe;
+    /// #
+    /// # fn main() -> Result<(), telegram_bot::Error> {
+    /// # let telegram_token = "token";
+    /// # let api = Api::new(telegram_token)?;
+    /// # if false {
+    /// let result = api.send(GetMe);
+    /// println!("{:?}", result);
+    /// # }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send<Req: Request>(
+        &self,
+        request: Req,
+    ) -> Result<<Req::Response as ResponseType>::Type, Error> {
+        let future = self.api.send(request);
+        self.runtime.lock().unwrap().block_on(future)
+    }
+
+    /// Send a request to the Telegram server and wait for a response, timing out after `duration`.
+    /// Returns `None` if timeout fired.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use telegram_bot::blocking::Api;
+    /// # use telegram_bot::GetMe;
+    /// # use std::time::Duration;
+    /// #
+    /// # fn main() -> Result<(), telegram_bot::Error> {
+    /// # let telegram_token = "token";
+    /// # let api = Api::new(telegram_token)?;
+    /// # if false {
+    /// let result = api.send_timeout(GetMe, Duration::from_secs(2));
+    /// println!("{:?}", result);
+    /// # }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send_timeout<Req: Request>(
+        &self,
+        request: Req,
+        duration: Duration,
+    ) -> Result<Option<<Req::Response as ResponseType>::Type>, Error> {
+        let future = self.api.send_timeout(request, duration);
+        self.runtime.lock().unwrap().block_on(future)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use telegram_bot_raw::{ChatId, DeleteMessage, GetMe, MessageId};
+
+    use super::*;
+    use crate::connector::MockConnector;
+
+    #[test]
+    fn test_send() {
+        let mock = MockConnector::new();
+        mock.push(MockConnector::ok(json!(true)));
+        mock.push(MockConnector::ok(json!({
+            "id": 1,
+            "is_bot": true,
+            "first_name": "Bot",
+        })));
+
+        let api = Api::with_connector("token", Box::new(mock.clone())).unwrap();
+        api.send(DeleteMessage::new(ChatId::new(42), MessageId::new(1)))
+            .unwrap();
+        let me = api
+            .send_timeout(GetMe, Duration::from_secs(1))
+            .unwrap()
+            .unwrap();
+        assert_eq!(me.first_name, "Bot");
+        assert!(mock.sent("deleteMessage", json!({"chat_id": 42, "message_id": 1})));
+    }
+}
@@ -0,0 +1,226 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use telegram_bot_raw::{CallbackQuery, InlineKeyboardButton};
+
+use crate::errors::{Error, ErrorKind};
+
+/// Maximum length of callback data in bytes.
+pub const CALLBACK_DATA_MAX_LENGTH: usize = 64;
+
+const CALLBACK_LOOKUP_PREFIX: char = '#';
+const CALLBACK_LOOKUP_KEY_LENGTH: usize = 16;
+
+/// Codec of typed values to callback data of inline keyboard buttons and back.
+///
+/// Values are encoded with serde to JSON. Field and variant names are part of the data,
+/// so prefer tuples, tuple variants and short names, e.g. with `#[serde(rename = "v")]`,
+/// to keep the data compact. As in JSON, `Some(None)` and `Some(())` are decoded as `None`.
+///
+/// Telegram limits callback data to 64 bytes, encoding longer payloads fails, unless
+/// the [`lookup_table`](#method.lookup_table) is enabled. In this case long payloads
+/// are kept in memory of the bot and the callback data contains only a random key.
+///
+/// # Examples
+///
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+/// use telegram_bot::{CallbackCodec, InlineKeyboardMarkup};
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// enum Action {
+///     Vote(u32, u8),
+///     Cancel,
+/// }
+///
+/// # fn main() -> Result<(), telegram_bot::Error> {
+/// let codec = CallbackCodec::new();
+///
+/// let mut keyboard = InlineKeyboardMarkup::new();
+/// keyboard.add_row(vec![
+///     codec.button("Yes", &Action::Vote(12, 0))?,
+///     codec.button("Cancel", &Action::Cancel)?,
+/// ]);
+///
+/// assert_eq!(codec.encode(&Action::Vote(12, 0))?, r#"{"Vote":[12,0]}"#);
+/// assert_eq!(codec.decode::<Action>(r#""Cancel""#)?, Action::Cancel);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CallbackCodec {
+    table: Option<Arc<Mutex<LookupTable>>>,
+}
+
+#[derive(Debug)]
+struct LookupTable {
+    capacity: usize,
+    keys: VecDeque<String>,
+    payloads: HashMap<String, String>,
+}
+
+impl CallbackCodec {
+    /// Create a new `CallbackCodec` instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep payloads longer than 64 bytes in memory, at most `capacity` of them,
+    /// the oldest payloads are evicted first. Buttons with evicted payloads,
+    /// or created before a restart of the bot, can't be decoded.
+    ///
+    /// Disabled by default.
+    pub fn lookup_table(&mut self, capacity: usize) -> &mut Self {
+        self.table = Some(Arc::new(Mutex::new(LookupTable {
+            capacity,
+            keys: VecDeque::new(),
+            payloads: HashMap::new(),
+        })));
+        self
+    }
+
+    /// Encode the `value` to callback data.
+    pub fn encode<T: Serialize>(&self, value: &T) -> Result<String, Error> {
+        let data = serde_json::to_string(value).map_err(ErrorKind::from)?;
+        if data.len() <= CALLBACK_DATA_MAX_LENGTH {
+            return Ok(data);
+        }
+
+        let table = match self.table {
+            Some(ref table) => table,
+            None => return Err(ErrorKind::CallbackDataTooLong(data.len()).into()),
+        };
+        let key: String = thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(CALLBACK_LOOKUP_KEY_LENGTH)
+            .collect();
+
+        let mut table = table.lock().unwrap();
+        while table.keys.len() >= table.capacity.max(1) {
+            if let Some(evicted) = table.keys.pop_front() {
+                table.payloads.remove(&evicted);
+            }
+        }
+        table.keys.push_back(key.clone());
+        table.payloads.insert(key.clone(), data);
+        Ok(format!("{}{}", CALLBACK_LOOKUP_PREFIX, key))
+    }
+
+    /// Decode the callback `data` to a value.
+    pub fn decode<T: DeserializeOwned>(&self, data: &str) -> Result<T, Error> {
+        if !data.starts_with(CALLBACK_LOOKUP_PREFIX) {
+            return serde_json::from_str(data).map_err(|error| ErrorKind::from(error).into());
+        }
+
+        let table = self.table.as_ref().map(|table| table.lock().unwrap());
+        match table.and_then(|table| table.payloads.get(&data[1..]).cloned()) {
+            Some(payload) => {
+                serde_json::from_str(&payload).map_err(|error| ErrorKind::from(error).into())
+            }
+            None => Err(ErrorKind::InvalidCallbackData(data.to_string()).into()),
+        }
+    }
+
+    /// Create a button with the `value` as callback data.
+    pub fn button<S: AsRef<str>, T: Serialize>(
+        &self,
+        text: S,
+        value: &T,
+    ) -> Result<InlineKeyboardButton, Error> {
+        Ok(InlineKeyboardButton::callback(text, self.encode(value)?))
+    }
+
+    /// Decode callback data of the `query`.
+    pub fn decode_query<T: DeserializeOwned>(&self, query: &CallbackQuery) -> Result<T, Error> {
+        match query.data {
+            Some(ref data) => self.decode(data),
+            None => Err(ErrorKind::InvalidCallbackData(String::new()).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::fmt;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    enum Action {
+        Open,
+        Page(u32),
+        Move(i8, i8),
+        Vote {
+            poll_id: u64,
+            option: Option<String>,
+        },
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Payload(Action, Vec<char>, BTreeMap<String, u8>, bool, ());
+
+    fn roundtrip<T>(codec: &CallbackCodec, value: T) -> String
+    where
+        T: Serialize + DeserializeOwned + PartialEq + fmt::Debug,
+    {
+        let data = codec.encode(&value).unwrap();
+        assert_eq!(codec.decode::<T>(&data).unwrap(), value);
+        data
+    }
+
+    #[test]
+    fn test_encoding() {
+        let codec = CallbackCodec::new();
+        assert_eq!(roundtrip(&codec, Action::Open), r#""Open""#);
+        assert_eq!(roundtrip(&codec, Action::Page(3)), r#"{"Page":3}"#);
+        assert_eq!(roundtrip(&codec, Action::Move(-1, 2)), r#"{"Move":[-1,2]}"#);
+        let vote = Action::Vote {
+            poll_id: 7,
+            option: Some("yes".to_string()),
+        };
+        assert_eq!(
+            roundtrip(&codec, vote),
+            r#"{"Vote":{"poll_id":7,"option":"yes"}}"#
+        );
+
+        let mut counts = BTreeMap::new();
+        counts.insert("a".to_string(), 1);
+        let payload = Payload(Action::Page(1), vec!['x'], counts, true, ());
+        assert_eq!(
+            roundtrip(&codec, payload),
+            r#"[{"Page":1},["x"],{"a":1},true,null]"#
+        );
+
+        assert_eq!(roundtrip(&codec, Some(Some(1u8))), "1");
+        assert_eq!(codec.encode(&Some(None::<u8>)).unwrap(), "null");
+        assert_eq!(codec.decode::<Option<Option<u8>>>("null").unwrap(), None);
+        assert_eq!(codec.decode::<Option<()>>("null").unwrap(), None);
+
+        assert!(codec.decode::<Action>(r#""Close""#).is_err());
+        assert!(codec.decode::<Action>("[0]").is_err());
+    }
+
+    #[test]
+    fn test_length() {
+        let long = "x".repeat(CALLBACK_DATA_MAX_LENGTH);
+        let codec = CallbackCodec::new();
+        let error = codec.button("Button", &long).unwrap_err();
+        assert_eq!(error.to_string(), "callback data is too long: 66 bytes");
+
+        let mut codec = CallbackCodec::new();
+        codec.lookup_table(1);
+        let first = roundtrip(&codec, long.clone());
+        assert!(first.starts_with('#') && first.len() <= CALLBACK_DATA_MAX_LENGTH);
+
+        roundtrip(&codec, format!("{}y", long));
+        assert!(codec.decode::<String>(&first).is_err());
+        assert_eq!(codec.encode(&"short").unwrap(), r#""short""#);
+    }
+}
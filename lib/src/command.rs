@@ -0,0 +1,303 @@
+//! Parsing of bot commands.
+
+use std::error;
+use std::fmt;
+use std::str::FromStr;
+
+use telegram_bot_raw::{Message, MessageEntity, MessageEntityKind, MessageKind};
+
+/// Bot command parsed from a message, e.g. `/ban@bot_name 42 "spam links"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Command {
+    /// Name of the command without the leading slash and the bot username.
+    pub name: String,
+    /// Arguments split by whitespace, quoted arguments may contain whitespace.
+    pub args: Vec<String>,
+    /// Text following the command, with surrounding whitespace removed.
+    pub rest: String,
+}
+
+impl Command {
+    /// Parse the command at the beginning of the `message`.
+    ///
+    /// The command is recognized by the `BotCommand` entity. If the command has
+    /// the `@bot_name` suffix, it has to match `username` of the bot, which is obtained
+    /// from the [`GetMe`](../telegram_bot_raw/requests/get_me/struct.GetMe.html) request.
+    ///
+    /// Returns `None` if the message doesn't start with a command, or the command
+    /// is addressed to another bot.
+    pub fn parse(message: &Message, username: &str) -> Option<Command> {
+        match message.kind {
+            MessageKind::Text {
+                ref data,
+                ref entities,
+            } => Self::parse_text(data, entities, username),
+            _ => None,
+        }
+    }
+
+    /// Parse the command at the beginning of the `text` with `entities`,
+    /// see [`parse`](#method.parse) for details.
+    pub fn parse_text(text: &str, entities: &[MessageEntity], username: &str) -> Option<Command> {
+        let entity = entities
+            .iter()
+            .find(|entity| entity.kind == MessageEntityKind::BotCommand && entity.offset == 0)?;
+        let range = entity.range(text)?;
+
+        let command = text[range.clone()].trim_start_matches('/');
+        let name = match command.find('@') {
+            Some(index) => {
+                let suffix = &command[index + 1..];
+                if !suffix.eq_ignore_ascii_case(username.trim_start_matches('@')) {
+                    return None;
+                }
+                &command[..index]
+            }
+            None => command,
+        };
+
+        let rest = text[range.end..].trim();
+        Some(Command {
+            name: name.to_string(),
+            args: split_args(rest),
+            rest: rest.to_string(),
+        })
+    }
+
+    /// Parse the argument at `index`.
+    pub fn arg<T>(&self, index: usize) -> Result<T, CommandError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        let value = self
+            .args
+            .get(index)
+            .ok_or_else(|| CommandError::WrongArgumentsCount {
+                expected: index + 1,
+                found: self.args.len(),
+            })?;
+        parse_arg(index, value)
+    }
+
+    /// Parse the whole text following the command as a single argument.
+    pub fn parse_rest<T>(&self) -> Result<T, CommandError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        parse_arg(0, &self.rest)
+    }
+
+    /// Check that the command has exactly `count` arguments.
+    pub fn expect_args(&self, count: usize) -> Result<(), CommandError> {
+        if self.args.len() != count {
+            return Err(CommandError::WrongArgumentsCount {
+                expected: count,
+                found: self.args.len(),
+            });
+        }
+        Ok(())
+    }
+}
+
+fn parse_arg<T>(index: usize, value: &str) -> Result<T, CommandError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    value
+        .parse()
+        .map_err(|error: T::Err| CommandError::InvalidArgument {
+            index,
+            value: value.to_string(),
+            error: error.to_string(),
+        })
+}
+
+/// Split command arguments by whitespace. Arguments in double quotes may contain
+/// whitespace, `\"` and `\\` inside quotes stand for a quote and a backslash.
+fn split_args(text: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    loop {
+        while chars.peek().map(|ch| ch.is_whitespace()) == Some(true) {
+            chars.next();
+        }
+        let first = match chars.next() {
+            Some(ch) => ch,
+            None => break,
+        };
+
+        let mut arg = String::new();
+        if first == '"' {
+            while let Some(ch) = chars.next() {
+                match ch {
+                    '"' => break,
+                    '\\' if chars.peek() == Some(&'"') || chars.peek() == Some(&'\\') => {
+                        arg.extend(chars.next());
+                    }
+                    ch => arg.push(ch),
+                }
+            }
+        } else {
+            arg.push(first);
+            while let Some(&ch) = chars.peek() {
+                if ch.is_whitespace() {
+                    break;
+                }
+                arg.push(ch);
+                chars.next();
+            }
+        }
+        args.push(arg);
+    }
+
+    args
+}
+
+/// Enum of bot commands, usually implemented with `#[derive(BotCommands)]`.
+///
+/// The derive macro maps every variant to a command named after the variant in snake case,
+/// the fields are parsed from the arguments in order using `FromStr`. The following
+/// attributes can be used on variants:
+///
+/// * `#[command(rename = "name")]` sets the name of the command;
+/// * `#[command(description = "text")]` sets the description of the command,
+///   the doc comment of the variant is used by default;
+/// * `#[command(rest)]` parses the whole text following the command into the only field.
+///
+/// # Examples
+///
+/// ```rust
+/// use telegram_bot::{BotCommands, Command, CommandError};
+///
+/// #[derive(Debug, PartialEq, BotCommands)]
+/// enum Commands {
+///     /// Show the help.
+///     Help,
+///     /// Ban a user.
+///     Ban { user_id: i64, reason: String },
+///     #[command(rename = "echo", description = "Repeat the text.", rest)]
+///     Say(String),
+/// }
+///
+/// let command = Command {
+///     name: "ban".to_string(),
+///     args: vec!["42".to_string(), "spam links".to_string()],
+///     rest: "42 \"spam links\"".to_string(),
+/// };
+/// let expected = Commands::Ban { user_id: 42, reason: "spam links".to_string() };
+/// assert_eq!(Commands::from_command(&command), Ok(expected));
+/// assert_eq!(
+///     Commands::help(),
+///     "/help - Show the help.\n/ban - Ban a user.\n/echo - Repeat the text."
+/// );
+/// ```
+pub trait BotCommands: Sized {
+    /// Convert the parsed `command` to a variant.
+    fn from_command(command: &Command) -> Result<Self, CommandError>;
+
+    /// Names and descriptions of all commands.
+    fn commands() -> Vec<(&'static str, &'static str)>;
+
+    /// Parse the command at the beginning of the `message`, see
+    /// [`Command::parse`](struct.Command.html#method.parse) for details.
+    fn parse(message: &Message, username: &str) -> Option<Result<Self, CommandError>> {
+        Command::parse(message, username).map(|command| Self::from_command(&command))
+    }
+
+    /// Help text listing all commands with their descriptions.
+    fn help() -> String {
+        Self::commands()
+            .into_iter()
+            .map(|(name, description)| match description {
+                "" => format!("/{}", name),
+                description => format!("/{} - {}", name, description),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Error of converting a command to a [`BotCommands`](trait.BotCommands.html) variant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandError {
+    /// There is no command with this name.
+    UnknownCommand(String),
+    /// The command has a wrong number of arguments.
+    WrongArgumentsCount { expected: usize, found: usize },
+    /// The argument at `index` can't be parsed.
+    InvalidArgument {
+        index: usize,
+        value: String,
+        error: String,
+    },
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CommandError::UnknownCommand(name) => write!(f, "unknown command /{}", name),
+            CommandError::WrongArgumentsCount { expected, found } => {
+                write!(f, "expected {} arguments, found {}", expected, found)
+            }
+            CommandError::InvalidArgument {
+                index,
+                value,
+                error,
+            } => write!(f, "invalid argument {} {:?}: {}", index + 1, value, error),
+        }
+    }
+}
+
+impl error::Error for CommandError {}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn message(text: &str, length: usize) -> Message {
+        serde_json::from_value(json!({
+            "message_id": 1,
+            "date": 0,
+            "from": {"id": 42, "is_bot": false, "first_name": "John"},
+            "chat": {"id": 42, "type": "private", "first_name": "John"},
+            "text": text,
+            "entities": [{"type": "bot_command", "offset": 0, "length": length}],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let command = Command::parse(&message("/ban@MyBot 42 \"spam links\"", 10), "mybot");
+        assert_eq!(
+            command,
+            Some(Command {
+                name: "ban".to_string(),
+                args: vec!["42".to_string(), "spam links".to_string()],
+                rest: "42 \"spam links\"".to_string(),
+            })
+        );
+        assert_eq!(command.unwrap().arg::<i64>(0), Ok(42));
+
+        assert!(Command::parse(&message("/ban@OtherBot 42", 13), "mybot").is_none());
+        assert!(Command::parse_text("ban 42", &[], "mybot").is_none());
+
+        let command = Command::parse(&message("/start", 6), "mybot").unwrap();
+        assert_eq!(command.name, "start");
+        assert!(command.args.is_empty());
+    }
+
+    #[test]
+    fn test_split_args() {
+        assert_eq!(
+            split_args(r#" a  "b c" "d \"e\"" "" 🦀 "unterminated "#),
+            vec!["a", "b c", "d \"e\"", "", "🦀", "unterminated "]
+        );
+    }
+}
@@ -0,0 +1,288 @@
+//! Connector which records requests to a cassette file and replays them later.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use futures::{future, stream, Future, FutureExt, Stream};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::Mutex as AsyncMutex;
+
+use telegram_bot_raw::{ApiUrl, HttpRequest, HttpResponse};
+
+use super::mock::request_params;
+use super::Connector;
+use crate::errors::{Error, ErrorKind};
+
+const CASSETTE_REDACTED_TOKEN: &str = "<token>";
+
+/// Connector which records request/response pairs to a JSON cassette file and
+/// answers from the cassette without network access later.
+///
+/// In record mode every request is forwarded to the inner connector and the
+/// cassette file is rewritten after each response, the bot token is redacted.
+/// In replay mode a request is answered with the first recorded response to
+/// the same method with the same parameters, requests missing in the cassette fail.
+///
+/// File downloads are not recorded: they are forwarded in record mode
+/// and fail in replay mode.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use telegram_bot::connector::{default_connector, CassetteConnector};
+/// use telegram_bot::Api;
+///
+/// # fn main() -> Result<(), telegram_bot::Error> {
+/// # let telegram_token = "token";
+/// // Record a real session.
+/// let connector = CassetteConnector::record(default_connector(), "tests/session.json");
+/// let api = Api::with_connector(telegram_token, Box::new(connector));
+///
+/// // Replay it offline.
+/// let connector = CassetteConnector::replay("tests/session.json")?;
+/// let api = Api::with_connector(telegram_token, Box::new(connector));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct CassetteConnector {
+    mode: Mode,
+    cassette: Arc<Mutex<Cassette>>,
+    writing: Arc<AsyncMutex<()>>,
+}
+
+#[derive(Debug)]
+enum Mode {
+    Record {
+        inner: Arc<dyn Connector>,
+        path: PathBuf,
+    },
+    Replay,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Cassette {
+    interactions: Vec<Interaction>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Interaction {
+    method: String,
+    params: Value,
+    #[serde(default = "default_status")]
+    status: u16,
+    #[serde(default)]
+    headers: Vec<(String, String)>,
+    response: Value,
+}
+
+impl CassetteConnector {
+    /// Create a new `CassetteConnector` forwarding requests to the `inner` connector
+    /// and recording them to the cassette at `path`, existing file is overwritten.
+    pub fn record<P: AsRef<Path>>(inner: Box<dyn Connector>, path: P) -> Self {
+        CassetteConnector {
+            mode: Mode::Record {
+                inner: inner.into(),
+                path: path.as_ref().to_path_buf(),
+            },
+            cassette: Default::default(),
+            writing: Default::default(),
+        }
+    }
+
+    /// Create a new `CassetteConnector` answering requests from the cassette at `path`.
+    pub fn replay<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let data = fs::read(path).map_err(ErrorKind::from)?;
+        let cassette = serde_json::from_slice(&data).map_err(ErrorKind::from)?;
+        Ok(CassetteConnector {
+            mode: Mode::Replay,
+            cassette: Arc::new(Mutex::new(cassette)),
+            writing: Default::default(),
+        })
+    }
+
+    /// Number of interactions which were recorded, or are left to replay.
+    pub fn interactions(&self) -> usize {
+        self.cassette.lock().unwrap().interactions.len()
+    }
+}
+
+impl Connector for CassetteConnector {
+    fn request(
+        &self,
+        api_url: &ApiUrl,
+        token: &str,
+        req: HttpRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<HttpResponse, Error>> + Send>> {
+        let method = req.name().to_string();
+        let params = request_params(&req);
+
+        match self.mode {
+            Mode::Record {
+                ref inner,
+                ref path,
+            } => {
+                let token = token.to_string();
+                let future = inner.request(api_url, &token, req);
+                let path = path.clone();
+                let cassette = self.cassette.clone();
+                let writing = self.writing.clone();
+
+                async move {
+                    let response = future.await?;
+
+                    // Snapshots are taken while holding the write lock,
+                    // so an older one never overwrites a newer one.
+                    let _writing = writing.lock().await;
+                    let data = {
+                        let mut cassette = cassette.lock().unwrap();
+                        cassette.interactions.push(Interaction {
+                            method,
+                            params,
+                            status: response.status,
+                            headers: response.headers.clone(),
+                            response: response_to_value(&response),
+                        });
+                        serde_json::to_string_pretty(&*cassette).map_err(ErrorKind::from)?
+                    };
+                    let data = if token.is_empty() {
+                        data
+                    } else {
+                        data.replace(&token, CASSETTE_REDACTED_TOKEN)
+                    };
+                    tokio::fs::write(path, data)
+                        .await
+                        .map_err(ErrorKind::from)?;
+
+                    Ok(response)
+                }
+                .boxed()
+            }
+            Mode::Replay => {
+                let mut cassette = self.cassette.lock().unwrap();
+                let position = cassette.interactions.iter().position(|interaction| {
+                    interaction.method == method && interaction.params == params
+                });
+
+                let result = match position {
+                    Some(position) => {
+                        let interaction = cassette.interactions.remove(position);
+                        Ok(interaction.into_response())
+                    }
+                    None => Err(ErrorKind::UnexpectedRequest(method).into()),
+                };
+                Box::pin(future::ready(result))
+            }
+        }
+    }
+
+    fn download(
+        &self,
+        api_url: &ApiUrl,
+        token: &str,
+        file_path: &str,
+    ) -> Pin<Box<dyn Stream<Item = Result<Bytes, Error>> + Send>> {
+        match self.mode {
+            Mode::Record { ref inner, .. } => inner.download(api_url, token, file_path),
+            Mode::Replay => {
+                let error = ErrorKind::UnexpectedRequest(format!("download {}", file_path));
+                Box::pin(stream::iter(vec![Err(error.into())]))
+            }
+        }
+    }
+}
+
+fn response_to_value(response: &HttpResponse) -> Value {
+    match response.body {
+        Some(ref body) => serde_json::from_slice(body)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(body).into_owned())),
+        None => Value::Null,
+    }
+}
+
+impl Interaction {
+    fn into_response(self) -> HttpResponse {
+        let body = match self.response {
+            Value::Null => None,
+            Value::String(body) => Some(body.into_bytes()),
+            value => Some(value.to_string().into_bytes()),
+        };
+        HttpResponse {
+            status: self.status,
+            headers: self.headers,
+            body,
+        }
+    }
+}
+
+fn default_status() -> u16 {
+    200
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use telegram_bot_raw::{ChatId, DeleteMessage, MessageId};
+
+    use super::*;
+    use crate::connector::MockConnector;
+    use crate::test_util::temp_path;
+    use crate::Api;
+
+    #[tokio::test]
+    async fn test_record_replay() {
+        let path = temp_path("cassette.json");
+
+        let mock = MockConnector::new();
+        mock.push(MockConnector::ok(json!(true)));
+        let connector = CassetteConnector::record(Box::new(mock), &path);
+        let api = Api::with_connector("secret-token", Box::new(connector));
+        api.send(DeleteMessage::new(ChatId::new(42), MessageId::new(1)))
+            .await
+            .unwrap();
+
+        let data = fs::read_to_string(&path).unwrap();
+        assert!(data.contains("deleteMessage"));
+        assert!(!data.contains("secret-token"));
+
+        let connector = CassetteConnector::replay(&path).unwrap();
+        assert_eq!(connector.interactions(), 1);
+        let api = Api::with_connector("other-token", Box::new(connector));
+        assert!(api
+            .send(DeleteMessage::new(ChatId::new(43), MessageId::new(1)))
+            .await
+            .is_err());
+        api.send(DeleteMessage::new(ChatId::new(42), MessageId::new(1)))
+            .await
+            .unwrap();
+        assert!(api
+            .send(DeleteMessage::new(ChatId::new(42), MessageId::new(1)))
+            .await
+            .is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_record_empty_token() {
+        let path = temp_path("cassette-empty-token.json");
+
+        let mock = MockConnector::new();
+        mock.push(MockConnector::ok(json!(true)));
+        let connector = CassetteConnector::record(Box::new(mock), &path);
+        let api = Api::with_connector("", Box::new(connector));
+        api.send(DeleteMessage::new(ChatId::new(42), MessageId::new(1)))
+            .await
+            .unwrap();
+
+        let data = fs::read_to_string(&path).unwrap();
+        assert!(data.contains("deleteMessage"));
+        assert!(!data.contains(CASSETTE_REDACTED_TOKEN));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
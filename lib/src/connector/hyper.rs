@@ -1,14 +1,14 @@
-use std::io::{Cursor, Read};
-use std::path::Path;
+#[cfg(feature = "rustls")]
+use std::io;
 use std::pin::Pin;
 use std::str::FromStr;
 
 use bytes::Bytes;
-use futures::{Future, FutureExt};
+use futures::{Future, FutureExt, Stream, StreamExt, TryFutureExt, TryStreamExt};
 use hyper::{
     body::to_bytes,
     client::{connect::Connect, Client},
-    header::CONTENT_TYPE,
+    header::{CONTENT_LENGTH, CONTENT_TYPE},
     http::Error as HttpError,
     Method, Request, Uri,
 };
@@ -16,22 +16,18 @@ use hyper::{
 use hyper_rustls::HttpsConnector;
 #[cfg(feature = "openssl")]
 use hyper_tls::HttpsConnector;
-use multipart::client::lazy::Multipart;
 use telegram_bot_raw::{
-    Body as TelegramBody, HttpRequest, HttpResponse, Method as TelegramMethod, MultipartValue, Text,
+    ApiUrl, Body as TelegramBody, HttpRequest, HttpResponse, Method as TelegramMethod,
 };
 
+use super::multipart::MultipartBody;
+use super::proxy::{Proxy, ProxyConnector};
 use super::Connector;
 use crate::errors::{Error, ErrorKind};
 
 #[derive(Debug)]
 pub struct HyperConnector<C>(Client<C>);
 
-enum MultipartTemporaryValue {
-    Text(Text),
-    Data { file_name: Text, data: Bytes },
-}
-
 impl<C> HyperConnector<C> {
     pub fn new(client: Client<C>) -> Self {
         HyperConnector(client)
@@ -41,10 +37,11 @@ impl<C> HyperConnector<C> {
 impl<C: Connect + std::fmt::Debug + 'static + Clone + Send + Sync> Connector for HyperConnector<C> {
     fn request(
         &self,
+        api_url: &ApiUrl,
         token: &str,
         req: HttpRequest,
     ) -> Pin<Box<dyn Future<Output = Result<HttpResponse, Error>> + Send>> {
-        let uri = Uri::from_str(&req.url.url(token));
+        let uri = Uri::from_str(&req.url.url(api_url, token));
         let client = self.0.clone();
 
         let future = async move {
@@ -70,94 +67,90 @@ impl<C: Connect + std::fmt::Debug + 'static + Clone + Send + Sync> Connector for
                     http_request.body(Into::<hyper::Body>::into(body))
                 }
                 TelegramBody::Multipart(parts) => {
-                    let mut fields = Vec::new();
-                    for (key, value) in parts {
-                        match value {
-                            MultipartValue::Text(text) => {
-                                fields.push((key, MultipartTemporaryValue::Text(text)))
-                            }
-                            MultipartValue::Path { file_name, path } => {
-                                let file_name = file_name
-                                    .or_else(|| {
-                                        AsRef::<Path>::as_ref(&path)
-                                            .file_name()
-                                            .and_then(|s| s.to_str())
-                                            .map(Into::into)
-                                    })
-                                    .ok_or(ErrorKind::InvalidMultipartFilename)?;
-
-                                let data = tokio::fs::read(path).await.map_err(ErrorKind::from)?;
-                                fields.push((
-                                    key,
-                                    MultipartTemporaryValue::Data {
-                                        file_name,
-                                        data: data.into(),
-                                    },
-                                ))
-                            }
-                            MultipartValue::Data { file_name, data } => fields
-                                .push((key, MultipartTemporaryValue::Data { file_name, data })),
-                        }
-                    }
-
-                    let mut prepared = {
-                        let mut part = Multipart::new();
-                        for (key, value) in &fields {
-                            match value {
-                                MultipartTemporaryValue::Text(text) => {
-                                    part.add_text(*key, text.as_str());
-                                }
-                                MultipartTemporaryValue::Data { file_name, data } => {
-                                    part.add_stream(
-                                        *key,
-                                        Cursor::new(data),
-                                        Some(file_name.as_str()),
-                                        None,
-                                    );
-                                }
-                            }
-                        }
-                        part.prepare().map_err(|err| err.error)
-                    }
+                    let multipart = MultipartBody::prepare(parts).await?;
+
+                    let content_type = format!(
+                        "multipart/form-data;boundary={bound}",
+                        bound = multipart.boundary()
+                    )
+                    .parse()
+                    .map_err(HttpError::from)
                     .map_err(ErrorKind::from)?;
-
-                    let boundary = prepared.boundary();
-
-                    let content_type =
-                        format!("multipart/form-data;boundary={bound}", bound = boundary)
-                            .parse()
-                            .map_err(HttpError::from)
-                            .map_err(ErrorKind::from)?;
+                    let content_length = multipart.content_length().into();
                     http_request.headers_mut().map(move |headers| {
                         headers.insert(CONTENT_TYPE, content_type);
+                        headers.insert(CONTENT_LENGTH, content_length);
                     });
 
-                    let mut bytes = Vec::new();
-                    prepared.read_to_end(&mut bytes).map_err(ErrorKind::from)?;
-                    http_request.body(bytes.into())
+                    http_request.body(hyper::Body::wrap_stream(multipart.into_stream()))
                 }
                 body => panic!("Unknown body type {:?}", body),
             }
             .map_err(ErrorKind::from)?;
 
             let response = client.request(request).await.map_err(ErrorKind::from)?;
-            let whole_chunk = to_bytes(response.into_body()).await;
-
-            let body = whole_chunk
+            let status = response.status().as_u16();
+            let headers = response
+                .headers()
                 .iter()
-                .fold(vec![], |mut acc, chunk| -> Vec<u8> {
-                    acc.extend_from_slice(&chunk);
-                    acc
-                });
-
-            Ok::<HttpResponse, Error>(HttpResponse { body: Some(body) })
+                .filter_map(|(name, value)| {
+                    let value = value.to_str().ok()?;
+                    Some((name.as_str().to_string(), value.to_string()))
+                })
+                .collect();
+            let body = to_bytes(response.into_body())
+                .await
+                .map_err(ErrorKind::from)?;
+
+            Ok::<HttpResponse, Error>(HttpResponse {
+                status,
+                headers,
+                body: Some(body.to_vec()),
+            })
         };
 
         future.boxed()
     }
+
+    fn download(
+        &self,
+        api_url: &ApiUrl,
+        token: &str,
+        file_path: &str,
+    ) -> Pin<Box<dyn Stream<Item = Result<Bytes, Error>> + Send>> {
+        let uri = Uri::from_str(&api_url.file_url(token, file_path));
+        let client = self.0.clone();
+
+        let future = async move {
+            let uri = uri.map_err(HttpError::from).map_err(ErrorKind::from)?;
+            let response = client.get(uri).await.map_err(ErrorKind::from)?;
+            if !response.status().is_success() {
+                return Err(ErrorKind::UnexpectedStatus(response.status()).into());
+            }
+
+            Ok::<_, Error>(
+                response
+                    .into_body()
+                    .map_err(|error| Error::from(ErrorKind::from(error))),
+            )
+        };
+
+        future.try_flatten_stream().boxed()
+    }
 }
 
+/// Default connector, requests are sent through the proxy configured by
+/// `HTTPS_PROXY` or `ALL_PROXY` environment variables if any.
+///
+/// Invalid proxy configuration is logged and requests are sent directly.
 pub fn default_connector() -> Result<Box<dyn Connector>, Error> {
+    if let Some(proxy) = Proxy::from_env() {
+        match proxy.and_then(proxy_connector) {
+            Ok(connector) => return Ok(connector),
+            Err(error) => tracing::warn!(error = %error, "ignoring proxy configuration"),
+        }
+    }
+
     #[cfg(feature = "rustls")]
     let connector = HttpsConnector::new();
 
@@ -168,3 +161,28 @@ pub fn default_connector() -> Result<Box<dyn Connector>, Error> {
         Client::builder().build(connector),
     )))
 }
+
+/// Connector which sends requests through the `proxy`.
+pub fn proxy_connector(proxy: Proxy) -> Result<Box<dyn Connector>, Error> {
+    let proxy = ProxyConnector::new(proxy);
+
+    #[cfg(feature = "rustls")]
+    let connector = {
+        let mut config = rustls_tls::ClientConfig::new();
+        config.root_store = rustls_native_certs::load_native_certs().map_err(|_| {
+            let error = io::Error::new(
+                io::ErrorKind::NotFound,
+                "unable to load native certificates",
+            );
+            ErrorKind::from(error)
+        })?;
+        HttpsConnector::from((proxy, config))
+    };
+
+    #[cfg(feature = "openssl")]
+    let connector = HttpsConnector::new_with_connector(proxy);
+
+    Ok(Box::new(HyperConnector::new(
+        Client::builder().build(connector),
+    )))
+}
@@ -0,0 +1,313 @@
+//! In-memory connector for testing bots without network access.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use futures::{future, stream, Future, Stream};
+use hyper::StatusCode;
+use serde_json::{json, Map, Value};
+
+use telegram_bot_raw::{ApiUrl, Body, HttpRequest, HttpResponse, MultipartValue};
+
+use super::Connector;
+use crate::errors::{Error, ErrorKind};
+
+type Handler = Arc<dyn Fn(&MockRequest) -> HttpResponse + Send + Sync>;
+
+/// Connector which records every request and answers with scripted responses.
+///
+/// A response is taken from the handler registered for the request method with
+/// [`on`](#method.on), otherwise from the queue filled with [`push`](#method.push).
+/// Requests without any scripted response are recorded and fail with
+/// `ErrorKind::UnexpectedRequest`.
+///
+/// The connector is a cheap handle, keep a clone to inspect recorded requests
+/// after passing it to the `Api`.
+///
+/// # Examples
+///
+/// ```rust
+/// use serde_json::json;
+/// use telegram_bot::connector::MockConnector;
+/// use telegram_bot::{Api, ChatId, SendMessage};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let mock = MockConnector::new();
+/// mock.push(MockConnector::ok(json!({
+///     "message_id": 1,
+///     "date": 0,
+///     "from": {"id": 1, "is_bot": true, "first_name": "Bot"},
+///     "chat": {"id": 42, "type": "private", "first_name": "John"},
+///     "text": "Hello!"
+/// })));
+///
+/// let api = Api::with_connector("token", Box::new(mock.clone()));
+/// api.send(SendMessage::new(ChatId::new(42), "Hello!")).await.unwrap();
+///
+/// assert!(mock.sent("sendMessage", json!({"chat_id": 42, "text": "Hello!"})));
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct MockConnector {
+    inner: Arc<Mutex<MockInner>>,
+}
+
+#[derive(Default)]
+struct MockInner {
+    requests: Vec<MockRequest>,
+    responses: VecDeque<HttpResponse>,
+    handlers: HashMap<String, Handler>,
+    files: HashMap<String, Bytes>,
+}
+
+/// Request recorded by the `MockConnector`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockRequest {
+    /// Name of the Telegram method, e.g. `sendMessage`.
+    pub method: String,
+    /// Request parameters as a JSON object.
+    ///
+    /// Multipart text fields are decoded as JSON if possible and kept as strings otherwise,
+    /// uploaded files are represented as `{"file_name": ..., "path": ...}` objects.
+    pub params: Value,
+}
+
+impl MockConnector {
+    /// Create a new `MockConnector` without scripted responses.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Successful Telegram response with the `result`.
+    pub fn ok(result: Value) -> HttpResponse {
+        response(200, json!({"ok": true, "result": result}))
+    }
+
+    /// Telegram error response with the `error_code` and `description`.
+    pub fn error(error_code: u16, description: &str) -> HttpResponse {
+        response(
+            error_code,
+            json!({"ok": false, "error_code": error_code, "description": description}),
+        )
+    }
+
+    /// Add the `response` to the queue of responses for requests without a handler.
+    pub fn push(&self, response: HttpResponse) -> &Self {
+        self.lock().responses.push_back(response);
+        self
+    }
+
+    /// Answer all requests to the `method` with the `handler`.
+    pub fn on<F>(&self, method: &str, handler: F) -> &Self
+    where
+        F: Fn(&MockRequest) -> HttpResponse + Send + Sync + 'static,
+    {
+        self.lock()
+            .handlers
+            .insert(method.to_string(), Arc::new(handler));
+        self
+    }
+
+    /// Serve the `data` as contents of the file with `file_path`.
+    pub fn file<D: Into<Bytes>>(&self, file_path: &str, data: D) -> &Self {
+        self.lock().files.insert(file_path.to_string(), data.into());
+        self
+    }
+
+    /// All recorded requests in order.
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.lock().requests.clone()
+    }
+
+    /// Recorded requests to the `method` in order.
+    pub fn requests_to(&self, method: &str) -> Vec<MockRequest> {
+        self.lock()
+            .requests
+            .iter()
+            .filter(|request| request.method == method)
+            .cloned()
+            .collect()
+    }
+
+    /// Returns `true` if a request to the `method` containing all of the `params` was issued.
+    /// Parameters of the request not mentioned in `params` are ignored.
+    pub fn sent(&self, method: &str, params: Value) -> bool {
+        self.lock()
+            .requests
+            .iter()
+            .any(|request| request.method == method && contains(&request.params, &params))
+    }
+
+    /// Forget recorded requests.
+    pub fn clear(&self) {
+        self.lock().requests.clear()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MockInner> {
+        self.inner.lock().unwrap()
+    }
+}
+
+impl fmt::Debug for MockConnector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = self.lock();
+        f.debug_struct("MockConnector")
+            .field("requests", &inner.requests)
+            .field("responses", &inner.responses.len())
+            .field("handlers", &inner.handlers.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Connector for MockConnector {
+    fn request(
+        &self,
+        _api_url: &ApiUrl,
+        _token: &str,
+        req: HttpRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<HttpResponse, Error>> + Send>> {
+        let request = MockRequest::from_http(&req);
+
+        let handler = self.lock().handlers.get(&request.method).cloned();
+        let response = match handler {
+            Some(handler) => Some(handler(&request)),
+            None => self.lock().responses.pop_front(),
+        };
+
+        let method = request.method.clone();
+        self.lock().requests.push(request);
+        match response {
+            Some(response) => Box::pin(future::ok(response)),
+            None => Box::pin(future::err(ErrorKind::UnexpectedRequest(method).into())),
+        }
+    }
+
+    fn download(
+        &self,
+        _api_url: &ApiUrl,
+        _token: &str,
+        file_path: &str,
+    ) -> Pin<Box<dyn Stream<Item = Result<Bytes, Error>> + Send>> {
+        let result = match self.lock().files.get(file_path) {
+            Some(data) => Ok(data.clone()),
+            None => Err(ErrorKind::UnexpectedStatus(StatusCode::NOT_FOUND).into()),
+        };
+        Box::pin(stream::iter(vec![result]))
+    }
+}
+
+impl MockRequest {
+    fn from_http(req: &HttpRequest) -> Self {
+        MockRequest {
+            method: req.name().to_string(),
+            params: request_params(req),
+        }
+    }
+}
+
+/// Parameters of the request as a JSON object, see `MockRequest::params`.
+pub(super) fn request_params(req: &HttpRequest) -> Value {
+    match req.body {
+        Body::Json(ref body) => serde_json::from_str(body).unwrap_or(Value::Null),
+        Body::Multipart(ref parts) => {
+            let mut params = Map::new();
+            for (key, value) in parts {
+                let value = match value {
+                    MultipartValue::Text(text) => serde_json::from_str(text.as_str())
+                        .unwrap_or_else(|_| Value::String(text.as_str().to_string())),
+                    MultipartValue::Path { path, file_name } => json!({
+                        "file_name": file_name.as_ref().map(|name| name.as_str()),
+                        "path": path.as_str(),
+                    }),
+                    MultipartValue::Data { file_name, .. } => json!({
+                        "file_name": file_name.as_str(),
+                    }),
+                };
+                params.insert(key.to_string(), value);
+            }
+            Value::Object(params)
+        }
+        _ => Value::Object(Map::new()),
+    }
+}
+
+fn response(status: u16, body: Value) -> HttpResponse {
+    HttpResponse {
+        status,
+        headers: vec![("content-type".to_string(), "application/json".to_string())],
+        body: Some(body.to_string().into_bytes()),
+    }
+}
+
+fn contains(value: &Value, expected: &Value) -> bool {
+    match (value, expected) {
+        (Value::Object(value), Value::Object(expected)) => {
+            expected.iter().all(|(key, expected)| {
+                value
+                    .get(key)
+                    .map(|value| contains(value, expected))
+                    .unwrap_or(false)
+            })
+        }
+        (value, expected) => value == expected,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use telegram_bot_raw::{ChatId, InputFileUpload, SendDocument, SendMessage};
+
+    use super::*;
+    use crate::Api;
+
+    #[tokio::test]
+    async fn test_mock_connector() {
+        let mock = MockConnector::new();
+        mock.on("sendDocument", |_| MockConnector::error(400, "Bad Request"));
+        mock.push(MockConnector::ok(json!({
+            "message_id": 1,
+            "date": 0,
+            "from": {"id": 1, "is_bot": true, "first_name": "Bot"},
+            "chat": {"id": 42, "type": "private", "first_name": "John"},
+            "text": "Hello!",
+        })));
+
+        let api = Api::with_connector("token", Box::new(mock.clone()));
+        api.send(SendMessage::new(ChatId::new(42), "Hello!"))
+            .await
+            .unwrap();
+        let document = InputFileUpload::with_path("../data/image.jpg");
+        assert!(api
+            .send(SendDocument::new(ChatId::new(42), document))
+            .await
+            .is_err());
+
+        assert_eq!(mock.requests().len(), 2);
+        assert!(mock.sent("sendMessage", json!({"chat_id": 42, "text": "Hello!"})));
+        assert!(!mock.sent("sendMessage", json!({"chat_id": 43})));
+        assert!(mock.sent(
+            "sendDocument",
+            json!({"chat_id": 42, "document": {"path": "../data/image.jpg"}})
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_mock_connector_unexpected_request() {
+        let mock = MockConnector::new();
+        let api = Api::with_connector("token", Box::new(mock.clone()));
+
+        let error = api
+            .send(SendMessage::new(ChatId::new(42), "Hello!"))
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "unexpected request: sendMessage");
+
+        assert!(mock.sent("sendMessage", json!({"chat_id": 42, "text": "Hello!"})));
+        mock.clear();
+        assert!(mock.requests().is_empty());
+    }
+}
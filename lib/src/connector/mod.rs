@@ -1,21 +1,49 @@
 //! Connector with hyper backend.
 
+mod cassette;
 pub mod hyper;
+mod mock;
+mod multipart;
+mod proxy;
+mod rate_limit;
 
 use std::fmt::Debug;
 use std::pin::Pin;
 
-use futures::Future;
-use telegram_bot_raw::{HttpRequest, HttpResponse};
+use bytes::Bytes;
+use futures::{future, stream, Future, Stream};
+use telegram_bot_raw::{ApiUrl, HttpRequest, HttpResponse};
 
-use crate::errors::Error;
+use crate::errors::{Error, ErrorKind};
+
+pub use self::cassette::CassetteConnector;
+pub use self::mock::{MockConnector, MockRequest};
+pub use self::proxy::Proxy;
+pub use self::rate_limit::RateLimitedConnector;
 
 pub trait Connector: Debug + Send + Sync {
+    /// Send the request to the Bot API server with `api_url`.
     fn request(
         &self,
+        api_url: &ApiUrl,
         token: &str,
         req: HttpRequest,
     ) -> Pin<Box<dyn Future<Output = Result<HttpResponse, Error>> + Send>>;
+
+    /// Download contents of the file with `file_path` obtained from the `GetFile` request
+    /// from the Bot API server with `api_url`.
+    ///
+    /// The default implementation fails, connectors have to override it to support downloads.
+    fn download(
+        &self,
+        _api_url: &ApiUrl,
+        _token: &str,
+        _file_path: &str,
+    ) -> Pin<Box<dyn Stream<Item = Result<Bytes, Error>> + Send>> {
+        Box::pin(stream::once(future::err(
+            ErrorKind::UnsupportedDownload.into(),
+        )))
+    }
 }
 
 pub fn default_connector() -> Box<dyn Connector> {
@@ -0,0 +1,184 @@
+//! Streaming `multipart/form-data` encoder.
+
+use std::io;
+use std::path::Path;
+
+use bytes::Bytes;
+use futures::{stream, Stream};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+
+use telegram_bot_raw::{Multipart, MultipartValue, Text};
+
+use crate::errors::{Error, ErrorKind};
+
+const BOUNDARY_LENGTH: usize = 32;
+const FILE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Multipart body which reads uploaded files lazily, chunk by chunk.
+pub(crate) struct MultipartBody {
+    boundary: String,
+    chunks: Vec<Chunk>,
+    content_length: u64,
+}
+
+enum Chunk {
+    Data(Bytes),
+    File(Text),
+}
+
+impl MultipartBody {
+    /// Prepare multipart body, only sizes of the uploaded files are queried at this point.
+    pub(crate) async fn prepare(parts: Multipart) -> Result<Self, Error> {
+        let boundary: String = thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(BOUNDARY_LENGTH)
+            .collect();
+
+        let mut chunks = Vec::new();
+        let mut content_length = 0;
+
+        for (key, value) in parts {
+            match value {
+                MultipartValue::Text(text) => {
+                    let header = format!(
+                        "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n",
+                        boundary, key
+                    );
+                    chunks.push(Chunk::Data(header.into()));
+                    chunks.push(Chunk::Data(format!("{}\r\n", text.as_str()).into()));
+                }
+                MultipartValue::Path { file_name, path } => {
+                    let file_name = file_name
+                        .or_else(|| {
+                            AsRef::<Path>::as_ref(&path)
+                                .file_name()
+                                .and_then(|s| s.to_str())
+                                .map(Into::into)
+                        })
+                        .ok_or(ErrorKind::InvalidMultipartFilename)?;
+
+                    let metadata = tokio::fs::metadata(AsRef::<Path>::as_ref(&path))
+                        .await
+                        .map_err(ErrorKind::from)?;
+
+                    content_length += metadata.len();
+                    chunks.push(Chunk::Data(file_header(&boundary, key, &file_name)));
+                    chunks.push(Chunk::File(path));
+                    chunks.push(Chunk::Data(Bytes::from_static(b"\r\n")));
+                }
+                MultipartValue::Data { file_name, data } => {
+                    chunks.push(Chunk::Data(file_header(&boundary, key, &file_name)));
+                    chunks.push(Chunk::Data(data));
+                    chunks.push(Chunk::Data(Bytes::from_static(b"\r\n")));
+                }
+            }
+        }
+        chunks.push(Chunk::Data(format!("--{}--\r\n", boundary).into()));
+
+        for chunk in &chunks {
+            if let Chunk::Data(data) = chunk {
+                content_length += data.len() as u64;
+            }
+        }
+
+        Ok(MultipartBody {
+            boundary,
+            chunks,
+            content_length,
+        })
+    }
+
+    pub(crate) fn boundary(&self) -> &str {
+        &self.boundary
+    }
+
+    pub(crate) fn content_length(&self) -> u64 {
+        self.content_length
+    }
+
+    /// Convert body into a stream of chunks, at most one file chunk is kept in memory.
+    pub(crate) fn into_stream(self) -> impl Stream<Item = Result<Bytes, io::Error>> + Send {
+        let state = (self.chunks.into_iter(), None::<File>);
+        stream::try_unfold(state, |(mut chunks, mut file)| async move {
+            loop {
+                if let Some(ref mut current) = file {
+                    let mut buffer = vec![0; FILE_CHUNK_SIZE];
+                    let read = current.read(&mut buffer).await?;
+                    if read > 0 {
+                        buffer.truncate(read);
+                        return Ok(Some((Bytes::from(buffer), (chunks, file))));
+                    }
+                    file = None;
+                }
+
+                match chunks.next() {
+                    None => return Ok(None),
+                    Some(Chunk::Data(data)) => return Ok(Some((data, (chunks, file)))),
+                    Some(Chunk::File(path)) => {
+                        file = Some(File::open(AsRef::<Path>::as_ref(&path)).await?);
+                    }
+                }
+            }
+        })
+    }
+}
+
+fn file_header(boundary: &str, key: &str, file_name: &Text) -> Bytes {
+    format!(
+        "--{}\r\nContent-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n\
+         Content-Type: application/octet-stream\r\n\r\n",
+        boundary,
+        key,
+        file_name.as_str().replace('"', "\\\"")
+    )
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::TryStreamExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_content_length() {
+        let parts = vec![
+            ("chat_id", MultipartValue::Text("42".into())),
+            (
+                "photo",
+                MultipartValue::Path {
+                    path: "../data/image.jpg".into(),
+                    file_name: None,
+                },
+            ),
+            (
+                "thumb",
+                MultipartValue::Data {
+                    file_name: "thumb\".jpg".into(),
+                    data: Bytes::from_static(b"thumb"),
+                },
+            ),
+        ];
+
+        let body = MultipartBody::prepare(parts).await.unwrap();
+        let boundary = body.boundary().to_string();
+        let content_length = body.content_length();
+        let chunks: Vec<Bytes> = body.into_stream().try_collect().await.unwrap();
+        let bytes = chunks.concat();
+
+        let image = std::fs::read("../data/image.jpg").unwrap();
+        assert!(image.len() > FILE_CHUNK_SIZE);
+        assert_eq!(bytes.len() as u64, content_length);
+
+        let text = String::from_utf8_lossy(&bytes);
+        assert!(text.starts_with(&format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"chat_id\"\r\n\r\n42\r\n",
+            boundary
+        )));
+        assert!(text.contains("name=\"photo\"; filename=\"image.jpg\""));
+        assert!(text.contains("name=\"thumb\"; filename=\"thumb\\\".jpg\""));
+        assert!(text.ends_with(&format!("thumb\r\n--{}--\r\n", boundary)));
+    }
+}
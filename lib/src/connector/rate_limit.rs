@@ -0,0 +1,374 @@
+//! Connector which throttles outgoing requests according to Telegram limits.
+
+use std::cmp::max;
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::{Future, FutureExt, Stream};
+use tokio::time::{delay_until, Instant};
+
+use telegram_bot_raw::{ApiUrl, ChatRef, HttpRequest, HttpResponse, Integer};
+
+use super::Connector;
+use crate::errors::Error;
+
+const TELEGRAM_GLOBAL_LIMIT_MESSAGES: u32 = 30;
+const TELEGRAM_GLOBAL_LIMIT_PERIOD_SECONDS: u64 = 1;
+const TELEGRAM_PRIVATE_CHAT_LIMIT_MESSAGES: u32 = 1;
+const TELEGRAM_PRIVATE_CHAT_LIMIT_PERIOD_SECONDS: u64 = 1;
+const TELEGRAM_GROUP_LIMIT_MESSAGES: u32 = 20;
+const TELEGRAM_GROUP_LIMIT_PERIOD_SECONDS: u64 = 60;
+
+/// Connector which delays requests addressed to a chat to stay within
+/// [Telegram limits](https://core.telegram.org/bots/faq#my-bot-is-hitting-limits-how-do-i-avoid-this):
+/// about 30 messages per second overall, 1 message per second to the same private
+/// chat and 20 messages per minute to the same group.
+///
+/// Only requests sending messages are limited: `send*` methods except `sendChatAction`,
+/// `forwardMessage` and `copyMessage`. Other requests, e.g. `getChat` or `editMessageText`,
+/// as well as requests without `chat_id` parameter are never delayed.
+///
+/// Requests are spaced evenly and queued instead of being rejected. Every chat is
+/// served in request order, chats waiting for their own limit don't hold back
+/// other chats. A request reserves its time slot when it is first polled, the slot
+/// is given back if the request is dropped before being sent and no later request
+/// to the chat has been queued.
+///
+/// Chats are told apart by the `chat_id` parameter as is, so requests to a channel
+/// by `@username` and by its numeric identifier are limited separately.
+///
+/// # Examples
+///
+/// ```rust
+/// use telegram_bot::connector::{default_connector, RateLimitedConnector};
+/// use telegram_bot::Api;
+///
+/// # let telegram_token = "token";
+/// let connector = RateLimitedConnector::new(default_connector());
+/// let api = Api::with_connector(telegram_token, Box::new(connector));
+/// ```
+#[derive(Debug)]
+pub struct RateLimitedConnector {
+    inner: Arc<dyn Connector>,
+    limits: Limits,
+    state: Arc<Mutex<RateLimiterState>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Limits {
+    global_interval: Duration,
+    private_chat_interval: Duration,
+    group_interval: Duration,
+}
+
+#[derive(Debug, Default)]
+struct RateLimiterState {
+    global_next: Option<Instant>,
+    chats_next: HashMap<ChatRef, Instant>,
+}
+
+impl RateLimitedConnector {
+    /// Create a new `RateLimitedConnector` with default Telegram limits wrapping `inner` connector.
+    pub fn new(inner: Box<dyn Connector>) -> Self {
+        RateLimitedConnector {
+            inner: inner.into(),
+            limits: Limits {
+                global_interval: interval(
+                    TELEGRAM_GLOBAL_LIMIT_MESSAGES,
+                    Duration::from_secs(TELEGRAM_GLOBAL_LIMIT_PERIOD_SECONDS),
+                ),
+                private_chat_interval: interval(
+                    TELEGRAM_PRIVATE_CHAT_LIMIT_MESSAGES,
+                    Duration::from_secs(TELEGRAM_PRIVATE_CHAT_LIMIT_PERIOD_SECONDS),
+                ),
+                group_interval: interval(
+                    TELEGRAM_GROUP_LIMIT_MESSAGES,
+                    Duration::from_secs(TELEGRAM_GROUP_LIMIT_PERIOD_SECONDS),
+                ),
+            },
+            state: Default::default(),
+        }
+    }
+
+    /// Set the limit of requests to all chats.
+    ///
+    /// Default limit is 30 requests per second.
+    pub fn global_limit(&mut self, requests: u32, period: Duration) -> &mut Self {
+        self.limits.global_interval = interval(requests, period);
+        self
+    }
+
+    /// Set the limit of requests to the same private chat.
+    ///
+    /// Default limit is 1 request per second.
+    pub fn private_chat_limit(&mut self, requests: u32, period: Duration) -> &mut Self {
+        self.limits.private_chat_interval = interval(requests, period);
+        self
+    }
+
+    /// Set the limit of requests to the same group, supergroup or channel.
+    ///
+    /// Default limit is 20 requests per minute.
+    pub fn group_limit(&mut self, requests: u32, period: Duration) -> &mut Self {
+        self.limits.group_interval = interval(requests, period);
+        self
+    }
+}
+
+impl Connector for RateLimitedConnector {
+    fn request(
+        &self,
+        api_url: &ApiUrl,
+        token: &str,
+        req: HttpRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<HttpResponse, Error>> + Send>> {
+        let chat = match req.chat_id() {
+            Some(chat) if is_rate_limited(req.name()) => chat,
+            _ => return self.inner.request(api_url, token, req),
+        };
+
+        let api_url = api_url.clone();
+        let token = token.to_string();
+        let inner = self.inner.clone();
+        let limits = self.limits;
+        let state = self.state.clone();
+
+        let future = async move {
+            let chat_interval = if is_private_chat(&chat) {
+                limits.private_chat_interval
+            } else {
+                limits.group_interval
+            };
+
+            let mut reservation = Reservation {
+                state,
+                chat: None,
+                global: None,
+            };
+
+            let at = reservation.reserve_chat(chat.clone(), chat_interval);
+            tracing::trace!(chat = %chat, at = ?at, "waiting for chat limit");
+            delay_until(at).await;
+
+            let at = reservation.reserve_global(limits.global_interval);
+            tracing::trace!(at = ?at, "waiting for global limit");
+            delay_until(at).await;
+
+            reservation.used();
+            inner.request(&api_url, &token, req).await
+        };
+
+        future.boxed()
+    }
+
+    fn download(
+        &self,
+        api_url: &ApiUrl,
+        token: &str,
+        file_path: &str,
+    ) -> Pin<Box<dyn Stream<Item = Result<Bytes, Error>> + Send>> {
+        self.inner.download(api_url, token, file_path)
+    }
+}
+
+impl RateLimiterState {
+    /// Reserve the earliest time slot for a request to the `chat`.
+    fn reserve_chat(&mut self, chat: ChatRef, interval: Duration, now: Instant) -> Instant {
+        self.chats_next.retain(|_, next| *next > now);
+        let next = self.chats_next.entry(chat).or_insert(now);
+        let at = max(*next, now);
+        *next = at + interval;
+        at
+    }
+
+    /// Reserve the earliest time slot for a request to any chat.
+    fn reserve_global(&mut self, interval: Duration, now: Instant) -> Instant {
+        let at = match self.global_next {
+            Some(next) => max(next, now),
+            None => now,
+        };
+        self.global_next = Some(at + interval);
+        at
+    }
+
+    /// Give back the time slot `at` reserved for a request to the `chat`,
+    /// if it is still the last reserved one.
+    fn release_chat(&mut self, chat: &ChatRef, at: Instant, interval: Duration) {
+        if let Some(next) = self.chats_next.get_mut(chat) {
+            if *next == at + interval {
+                *next = at;
+            }
+        }
+    }
+
+    /// Give back the time slot `at` reserved for a request to any chat,
+    /// if it is still the last reserved one.
+    fn release_global(&mut self, at: Instant, interval: Duration) {
+        if self.global_next == Some(at + interval) {
+            self.global_next = Some(at);
+        }
+    }
+}
+
+/// Time slots reserved by a request, given back if the request is dropped before being sent.
+struct Reservation {
+    state: Arc<Mutex<RateLimiterState>>,
+    chat: Option<(ChatRef, Instant, Duration)>,
+    global: Option<(Instant, Duration)>,
+}
+
+impl Reservation {
+    fn reserve_chat(&mut self, chat: ChatRef, interval: Duration) -> Instant {
+        let at = self
+            .state
+            .lock()
+            .unwrap()
+            .reserve_chat(chat.clone(), interval, Instant::now());
+        self.chat = Some((chat, at, interval));
+        at
+    }
+
+    fn reserve_global(&mut self, interval: Duration) -> Instant {
+        let at = self
+            .state
+            .lock()
+            .unwrap()
+            .reserve_global(interval, Instant::now());
+        self.global = Some((at, interval));
+        at
+    }
+
+    /// Keep the reserved time slots, the request is being sent.
+    fn used(&mut self) {
+        self.chat = None;
+        self.global = None;
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if self.chat.is_none() && self.global.is_none() {
+            return;
+        }
+        if let Ok(mut state) = self.state.lock() {
+            if let Some((chat, at, interval)) = self.chat.take() {
+                state.release_chat(&chat, at, interval);
+            }
+            if let Some((at, interval)) = self.global.take() {
+                state.release_global(at, interval);
+            }
+        }
+    }
+}
+
+fn interval(requests: u32, period: Duration) -> Duration {
+    period / max(requests, 1)
+}
+
+fn is_rate_limited(method: &str) -> bool {
+    match method {
+        "sendChatAction" => false,
+        "forwardMessage" | "copyMessage" => true,
+        method => method.starts_with("send"),
+    }
+}
+
+fn is_private_chat(chat: &ChatRef) -> bool {
+    match chat {
+        ChatRef::Id(id) => Integer::from(*id) > 0,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use telegram_bot_raw::{ChatId, GetChat, GetMe, Request, SendMessage};
+
+    use super::*;
+    use crate::connector::MockConnector;
+
+    fn send<R: Request>(
+        connector: &RateLimitedConnector,
+        request: R,
+    ) -> Pin<Box<dyn Future<Output = Result<HttpResponse, Error>> + Send>> {
+        connector.request(&ApiUrl::default(), "token", request.serialize().unwrap())
+    }
+
+    #[test]
+    fn test_reserve() {
+        let mut state = RateLimiterState::default();
+        let second = Duration::from_secs(1);
+        let now = Instant::now();
+
+        let user = ChatRef::from_chat_id(ChatId::new(42));
+        assert_eq!(state.reserve_chat(user.clone(), second, now), now);
+        assert_eq!(state.reserve_chat(user.clone(), second, now), now + second);
+        assert_eq!(
+            state.reserve_chat(user.clone(), second, now),
+            now + second * 2
+        );
+
+        let group = ChatRef::from_chat_id(ChatId::new(-42));
+        assert_eq!(state.reserve_chat(group, second * 3, now), now);
+
+        let later = now + second * 10;
+        assert_eq!(state.reserve_chat(user, second, later), later);
+        assert_eq!(state.chats_next.len(), 1);
+
+        let interval = second / 30;
+        assert_eq!(state.reserve_global(interval, now), now);
+        assert_eq!(state.reserve_global(interval, now), now + interval);
+        assert_eq!(state.reserve_global(interval, later), later);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_connector() {
+        let mock = MockConnector::new();
+        mock.on("sendMessage", |request| {
+            MockConnector::ok(request.params.clone())
+        });
+        mock.on("getChat", |_| MockConnector::ok(json!({})));
+        mock.on("getMe", |_| MockConnector::ok(json!({})));
+
+        let mut connector = RateLimitedConnector::new(Box::new(mock.clone()));
+        connector.private_chat_limit(1, Duration::from_millis(200));
+        let user = ChatId::new(42);
+        let millis = Duration::from_millis;
+
+        // The clock only moves when advanced, so delays are exact.
+        tokio::time::pause();
+
+        send(&connector, SendMessage::new(user, "1")).await.unwrap();
+        let mut second = send(&connector, SendMessage::new(user, "2"));
+        assert!(futures::poll!(second.as_mut()).is_pending());
+
+        // Lookups aren't delayed while a message to the same chat is waiting.
+        assert!(futures::poll!(send(&connector, GetChat::new(user))).is_ready());
+        assert!(futures::poll!(send(&connector, GetMe)).is_ready());
+
+        tokio::time::advance(millis(199)).await;
+        assert!(futures::poll!(second.as_mut()).is_pending());
+        tokio::time::advance(millis(1)).await;
+        assert!(futures::poll!(second.as_mut()).is_ready());
+
+        // A dropped request gives its time slot back.
+        let mut third = send(&connector, SendMessage::new(user, "3"));
+        assert!(futures::poll!(third.as_mut()).is_pending());
+        drop(third);
+        let mut fourth = send(&connector, SendMessage::new(user, "4"));
+        assert!(futures::poll!(fourth.as_mut()).is_pending());
+        tokio::time::advance(millis(200)).await;
+        assert!(futures::poll!(fourth.as_mut()).is_ready());
+
+        let texts: Vec<_> = mock
+            .requests_to("sendMessage")
+            .into_iter()
+            .map(|request| request.params["text"].clone())
+            .collect();
+        assert_eq!(texts, vec![json!("1"), json!("2"), json!("4")]);
+    }
+}
@@ -0,0 +1,429 @@
+//! Conversations spanning several updates.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::future::Future;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use telegram_bot_raw::{ChatId, Message, UserId};
+
+use crate::errors::{Error, ErrorKind};
+
+/// Identifier of a conversation with the user in the chat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DialogueKey {
+    pub chat_id: ChatId,
+    pub user_id: UserId,
+}
+
+impl DialogueKey {
+    /// Create a new `DialogueKey` instance.
+    pub fn new(chat_id: ChatId, user_id: UserId) -> Self {
+        DialogueKey { chat_id, user_id }
+    }
+}
+
+impl From<&Message> for DialogueKey {
+    fn from(message: &Message) -> Self {
+        DialogueKey::new(message.chat.id(), message.from.id)
+    }
+}
+
+/// State of a conversation together with the time of its last change.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DialogueEntry<S> {
+    pub state: S,
+    pub updated: SystemTime,
+}
+
+/// Storage of conversation states.
+pub trait DialogueStorage<S>: Send + Sync {
+    /// Load the state of the conversation, `None` if there is no such conversation.
+    fn get(&self, key: DialogueKey) -> Result<Option<DialogueEntry<S>>, Error>;
+
+    /// Save the state of the conversation.
+    fn set(&self, key: DialogueKey, entry: DialogueEntry<S>) -> Result<(), Error>;
+
+    /// Remove the conversation.
+    fn remove(&self, key: DialogueKey) -> Result<(), Error>;
+
+    /// Remove all conversations last changed before `time`.
+    fn remove_updated_before(&self, time: SystemTime) -> Result<(), Error>;
+}
+
+/// Dialogue storage which keeps states in memory, clones share the same states.
+#[derive(Debug)]
+pub struct MemoryDialogueStorage<S>(Arc<Mutex<HashMap<DialogueKey, DialogueEntry<S>>>>);
+
+impl<S> MemoryDialogueStorage<S> {
+    /// Create a new empty `MemoryDialogueStorage`.
+    pub fn new() -> Self {
+        MemoryDialogueStorage(Default::default())
+    }
+}
+
+impl<S> Clone for MemoryDialogueStorage<S> {
+    fn clone(&self) -> Self {
+        MemoryDialogueStorage(self.0.clone())
+    }
+}
+
+impl<S> Default for MemoryDialogueStorage<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: Clone + Send> DialogueStorage<S> for MemoryDialogueStorage<S> {
+    fn get(&self, key: DialogueKey) -> Result<Option<DialogueEntry<S>>, Error> {
+        Ok(self.0.lock().unwrap().get(&key).cloned())
+    }
+
+    fn set(&self, key: DialogueKey, entry: DialogueEntry<S>) -> Result<(), Error> {
+        self.0.lock().unwrap().insert(key, entry);
+        Ok(())
+    }
+
+    fn remove(&self, key: DialogueKey) -> Result<(), Error> {
+        self.0.lock().unwrap().remove(&key);
+        Ok(())
+    }
+
+    fn remove_updated_before(&self, time: SystemTime) -> Result<(), Error> {
+        self.0
+            .lock()
+            .unwrap()
+            .retain(|_, entry| entry.updated >= time);
+        Ok(())
+    }
+}
+
+/// Dialogue storage which keeps states in a JSON file.
+///
+/// States are cached in memory. After every change all of them are written to a new file,
+/// which is synced to disk and then replaces the old one, so the file is never left
+/// half-written after a crash. Writing blocks the current thread, so this storage
+/// suits bots with a moderate number of conversations.
+#[derive(Debug)]
+pub struct FileDialogueStorage<S> {
+    path: PathBuf,
+    entries: Mutex<HashMap<DialogueKey, DialogueEntry<S>>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredDialogue<S> {
+    key: DialogueKey,
+    #[serde(flatten)]
+    entry: DialogueEntry<S>,
+}
+
+impl<S: Serialize + DeserializeOwned + Clone> FileDialogueStorage<S> {
+    /// Open the storage keeping states in the file at `path`, the file is created
+    /// on the first change if it doesn't exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let entries = match fs::read(&path) {
+            Ok(data) => {
+                let stored: Vec<StoredDialogue<S>> =
+                    serde_json::from_slice(&data).map_err(ErrorKind::from)?;
+                stored
+                    .into_iter()
+                    .map(|stored| (stored.key, stored.entry))
+                    .collect()
+            }
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(error) => return Err(ErrorKind::from(error).into()),
+        };
+
+        Ok(FileDialogueStorage {
+            path,
+            entries: Mutex::new(entries),
+        })
+    }
+
+    fn save(&self, entries: &HashMap<DialogueKey, DialogueEntry<S>>) -> Result<(), Error> {
+        let stored: Vec<_> = entries
+            .iter()
+            .map(|(key, entry)| StoredDialogue {
+                key: *key,
+                entry: entry.clone(),
+            })
+            .collect();
+        let data = serde_json::to_vec(&stored).map_err(ErrorKind::from)?;
+
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+        let mut file = File::create(&temporary).map_err(ErrorKind::from)?;
+        file.write_all(&data).map_err(ErrorKind::from)?;
+        file.sync_all().map_err(ErrorKind::from)?;
+        fs::rename(&temporary, &self.path).map_err(ErrorKind::from)?;
+        Ok(())
+    }
+}
+
+impl<S> DialogueStorage<S> for FileDialogueStorage<S>
+where
+    S: Serialize + DeserializeOwned + Clone + Send,
+{
+    fn get(&self, key: DialogueKey) -> Result<Option<DialogueEntry<S>>, Error> {
+        Ok(self.entries.lock().unwrap().get(&key).cloned())
+    }
+
+    fn set(&self, key: DialogueKey, entry: DialogueEntry<S>) -> Result<(), Error> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.insert(key, entry);
+        self.save(&entries)
+    }
+
+    fn remove(&self, key: DialogueKey) -> Result<(), Error> {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.remove(&key).is_some() {
+            self.save(&entries)?;
+        }
+        Ok(())
+    }
+
+    fn remove_updated_before(&self, time: SystemTime) -> Result<(), Error> {
+        let mut entries = self.entries.lock().unwrap();
+        let count = entries.len();
+        entries.retain(|_, entry| entry.updated >= time);
+        if entries.len() != count {
+            self.save(&entries)?;
+        }
+        Ok(())
+    }
+}
+
+/// Conversations with users, which keep a state of type `S` for every chat and user
+/// in a [`DialogueStorage`](trait.DialogueStorage.html), clones share the same storage.
+///
+/// A conversation starts in the default state, a handler receives the current state
+/// and returns the next one, or `None` to finish the conversation. Conversations
+/// which were not changed for longer than the [`timeout`](#method.timeout) are expired.
+///
+/// Updates of one conversation must not be handled concurrently, which holds
+/// for the [`Dispatcher`](struct.Dispatcher.html) and the [`ChatRunner`](struct.ChatRunner.html).
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+/// use telegram_bot::{Api, CanReplySendMessage, Dialogues, Dispatcher, Filter};
+/// use telegram_bot::{MemoryDialogueStorage, MessageText};
+///
+/// #[derive(Clone)]
+/// enum SignUp {
+///     Start,
+///     Name,
+///     Age { name: String },
+/// }
+///
+/// impl Default for SignUp {
+///     fn default() -> Self {
+///         SignUp::Start
+///     }
+/// }
+///
+/// # let api: Api = Api::new("token");
+/// let mut dialogues = Dialogues::new(MemoryDialogueStorage::new());
+/// dialogues.timeout(Duration::from_secs(600));
+///
+/// let mut dispatcher = Dispatcher::new(&api);
+/// dispatcher.dialogue(Filter::private(), dialogues, |api, message, state| async move {
+///     let text = message.text().unwrap_or_default();
+///     match state {
+///         SignUp::Start => {
+///             api.send(message.text_reply("What is your name?")).await?;
+///             Ok(Some(SignUp::Name))
+///         }
+///         SignUp::Name => {
+///             api.send(message.text_reply("How old are you?")).await?;
+///             Ok(Some(SignUp::Age { name: text }))
+///         }
+///         SignUp::Age { name } => {
+///             let reply = format!("Welcome, {} ({})!", name, text);
+///             api.send(message.text_reply(reply)).await?;
+///             Ok(None)
+///         }
+///     }
+/// });
+/// ```
+pub struct Dialogues<S> {
+    storage: Arc<dyn DialogueStorage<S>>,
+    timeout: Option<Duration>,
+}
+
+impl<S> Dialogues<S> {
+    /// Create a new `Dialogues` instance keeping states in the `storage`.
+    pub fn new<T: DialogueStorage<S> + 'static>(storage: T) -> Self {
+        Dialogues {
+            storage: Arc::new(storage),
+            timeout: None,
+        }
+    }
+
+    /// Expire conversations which were not changed for longer than `timeout`.
+    ///
+    /// By default conversations never expire.
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Current state of the conversation, `None` if there is no conversation
+    /// or it's expired.
+    pub fn get(&self, key: DialogueKey) -> Result<Option<S>, Error> {
+        let entry = match self.storage.get(key)? {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+
+        if self.is_expired(&entry) {
+            tracing::trace!(key = ?key, "dialogue expired");
+            self.storage.remove(key)?;
+            return Ok(None);
+        }
+        Ok(Some(entry.state))
+    }
+
+    /// Set the state of the conversation, `None` finishes the conversation.
+    pub fn set(&self, key: DialogueKey, state: Option<S>) -> Result<(), Error> {
+        match state {
+            Some(state) => self.storage.set(
+                key,
+                DialogueEntry {
+                    state,
+                    updated: SystemTime::now(),
+                },
+            ),
+            None => self.storage.remove(key),
+        }
+    }
+
+    /// Remove all expired conversations from the storage.
+    pub fn remove_expired(&self) -> Result<(), Error> {
+        match self.timeout {
+            Some(timeout) => match SystemTime::now().checked_sub(timeout) {
+                Some(time) => self.storage.remove_updated_before(time),
+                None => Ok(()),
+            },
+            None => Ok(()),
+        }
+    }
+
+    /// Run the `handler` with the current state of the conversation, or the default state
+    /// if there is no conversation, and save the state returned by it.
+    pub async fn handle<H, F>(&self, key: DialogueKey, handler: H) -> Result<(), Error>
+    where
+        S: Default,
+        H: FnOnce(S) -> F,
+        F: Future<Output = Result<Option<S>, Error>>,
+    {
+        let state = self.get(key)?.unwrap_or_default();
+        let next = handler(state).await?;
+        self.set(key, next)
+    }
+
+    fn is_expired(&self, entry: &DialogueEntry<S>) -> bool {
+        match (self.timeout, entry.updated.elapsed()) {
+            (Some(timeout), Ok(elapsed)) => elapsed > timeout,
+            _ => false,
+        }
+    }
+}
+
+impl<S> Clone for Dialogues<S> {
+    fn clone(&self) -> Self {
+        Dialogues {
+            storage: self.storage.clone(),
+            timeout: self.timeout,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_path;
+
+    #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+    enum State {
+        #[default]
+        Start,
+        Name,
+        Age {
+            name: String,
+        },
+    }
+
+    #[tokio::test]
+    async fn test_handle() {
+        let dialogues = Dialogues::new(MemoryDialogueStorage::new());
+        let key = DialogueKey::new(ChatId::new(1), UserId::new(2));
+        let other = DialogueKey::new(ChatId::new(1), UserId::new(3));
+
+        dialogues
+            .handle(key, |state| async move {
+                assert_eq!(state, State::Start);
+                Ok(Some(State::Name))
+            })
+            .await
+            .unwrap();
+        assert_eq!(dialogues.get(key).unwrap(), Some(State::Name));
+        assert_eq!(dialogues.get(other).unwrap(), None);
+
+        dialogues.handle(key, |_| async { Ok(None) }).await.unwrap();
+        assert_eq!(dialogues.get(key).unwrap(), None);
+    }
+
+    #[test]
+    fn test_timeout() {
+        let storage = MemoryDialogueStorage::new();
+        let mut dialogues = Dialogues::new(storage.clone());
+        dialogues.timeout(Duration::from_secs(60));
+
+        let key = DialogueKey::new(ChatId::new(1), UserId::new(2));
+        let stale = DialogueKey::new(ChatId::new(1), UserId::new(3));
+        dialogues.set(key, Some(State::Name)).unwrap();
+        let entry = DialogueEntry {
+            state: State::Name,
+            updated: SystemTime::now() - Duration::from_secs(120),
+        };
+        storage.set(stale, entry.clone()).unwrap();
+
+        assert_eq!(dialogues.get(stale).unwrap(), None);
+        assert_eq!(storage.get(stale).unwrap(), None);
+
+        storage.set(stale, entry).unwrap();
+        dialogues.remove_expired().unwrap();
+        assert_eq!(storage.get(stale).unwrap(), None);
+        assert_eq!(dialogues.get(key).unwrap(), Some(State::Name));
+    }
+
+    #[test]
+    fn test_file_storage() {
+        let path = temp_path("dialogues.json");
+
+        let key = DialogueKey::new(ChatId::new(1), UserId::new(2));
+        let state = State::Age {
+            name: "John".to_string(),
+        };
+        let dialogues = Dialogues::new(FileDialogueStorage::open(&path).unwrap());
+        dialogues.set(key, Some(state.clone())).unwrap();
+
+        let dialogues: Dialogues<State> = Dialogues::new(FileDialogueStorage::open(&path).unwrap());
+        assert_eq!(dialogues.get(key).unwrap(), Some(state));
+        dialogues.set(key, None).unwrap();
+
+        let dialogues: Dialogues<State> = Dialogues::new(FileDialogueStorage::open(&path).unwrap());
+        assert_eq!(dialogues.get(key).unwrap(), None);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,552 @@
+//! Routing of updates to handlers.
+
+use std::fmt;
+use std::future::Future;
+use std::ops::Not;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use futures::{FutureExt, Stream, StreamExt};
+use regex::Regex;
+
+use telegram_bot_raw::{
+    CallbackQuery, InlineQuery, Message, MessageChat, MessageOrChannelPost, Poll, PollAnswer,
+    Update, UpdateKind, User, UserId,
+};
+
+use crate::api::Api;
+use crate::command::Command;
+use crate::dialogue::{DialogueKey, Dialogues};
+use crate::errors::Error;
+use crate::runner::ChatRunner;
+use crate::util::messages::MessageText;
+
+type Handler =
+    Box<dyn Fn(Api, Update) -> Option<BoxFuture<'static, Result<(), Error>>> + Send + Sync>;
+
+/// Dispatcher of updates to registered handlers.
+///
+/// Every handler is registered together with a [`Filter`](struct.Filter.html),
+/// an update is routed to the first handler in the order of registration
+/// which accepts the kind of the update and whose filter matches it.
+///
+/// # Examples
+///
+/// ```rust
+/// use telegram_bot::{Api, CanReplySendMessage, Dispatcher, Filter, MessageChat};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// # let api: Api = Api::new("token");
+/// let mut dispatcher = Dispatcher::new(&api);
+/// dispatcher
+///     .message(Filter::command("start", "bot_name"), |api, message| async move {
+///         api.send(message.text_reply("Welcome!")).await?;
+///         Ok(())
+///     })
+///     .message(
+///         Filter::text("(?i)^hello").and(Filter::chat(|chat| {
+///             matches!(chat, MessageChat::Private(_))
+///         })),
+///         |api, message| async move {
+///             api.send(message.text_reply("Hello!")).await?;
+///             Ok(())
+///         },
+///     )
+///     .callback_query(Filter::any(), |_, query| async move {
+///         println!("{:?}", query.data);
+///         Ok(())
+///     });
+///
+/// # if false {
+/// dispatcher.run(api.stream()).await;
+/// # }
+/// # }
+/// ```
+pub struct Dispatcher {
+    api: Api,
+    handlers: Vec<(Filter, Handler)>,
+}
+
+impl Dispatcher {
+    /// Create a new `Dispatcher` instance without handlers.
+    pub fn new(api: &Api) -> Self {
+        Dispatcher {
+            api: api.clone(),
+            handlers: Vec::new(),
+        }
+    }
+
+    /// Register a handler for new messages.
+    pub fn message<H, F>(&mut self, filter: Filter, handler: H) -> &mut Self
+    where
+        H: Fn(Api, Message) -> F + Send + Sync + 'static,
+        F: Future<Output = Result<(), Error>> + Send + 'static,
+    {
+        self.register(filter, handler, |kind| match kind {
+            UpdateKind::Message(message) => Some(message),
+            _ => None,
+        })
+    }
+
+    /// Register a handler for edited messages.
+    pub fn edited_message<H, F>(&mut self, filter: Filter, handler: H) -> &mut Self
+    where
+        H: Fn(Api, Message) -> F + Send + Sync + 'static,
+        F: Future<Output = Result<(), Error>> + Send + 'static,
+    {
+        self.register(filter, handler, |kind| match kind {
+            UpdateKind::EditedMessage(message) => Some(message),
+            _ => None,
+        })
+    }
+
+    /// Register a handler for callback queries.
+    pub fn callback_query<H, F>(&mut self, filter: Filter, handler: H) -> &mut Self
+    where
+        H: Fn(Api, CallbackQuery) -> F + Send + Sync + 'static,
+        F: Future<Output = Result<(), Error>> + Send + 'static,
+    {
+        self.register(filter, handler, |kind| match kind {
+            UpdateKind::CallbackQuery(query) => Some(query),
+            _ => None,
+        })
+    }
+
+    /// Register a handler for inline queries.
+    pub fn inline_query<H, F>(&mut self, filter: Filter, handler: H) -> &mut Self
+    where
+        H: Fn(Api, InlineQuery) -> F + Send + Sync + 'static,
+        F: Future<Output = Result<(), Error>> + Send + 'static,
+    {
+        self.register(filter, handler, |kind| match kind {
+            UpdateKind::InlineQuery(query) => Some(query),
+            _ => None,
+        })
+    }
+
+    /// Register a handler for poll states.
+    pub fn poll<H, F>(&mut self, filter: Filter, handler: H) -> &mut Self
+    where
+        H: Fn(Api, Poll) -> F + Send + Sync + 'static,
+        F: Future<Output = Result<(), Error>> + Send + 'static,
+    {
+        self.register(filter, handler, |kind| match kind {
+            UpdateKind::Poll(poll) => Some(poll),
+            _ => None,
+        })
+    }
+
+    /// Register a handler for poll answers.
+    pub fn poll_answer<H, F>(&mut self, filter: Filter, handler: H) -> &mut Self
+    where
+        H: Fn(Api, PollAnswer) -> F + Send + Sync + 'static,
+        F: Future<Output = Result<(), Error>> + Send + 'static,
+    {
+        self.register(filter, handler, |kind| match kind {
+            UpdateKind::PollAnswer(answer) => Some(answer),
+            _ => None,
+        })
+    }
+
+    /// Register a handler for multi-step conversations in messages. The handler receives
+    /// the state of the conversation with the sender of the message in the chat
+    /// and returns the next one, see [`Dialogues`](struct.Dialogues.html) for details.
+    pub fn dialogue<S, H, F>(
+        &mut self,
+        filter: Filter,
+        dialogues: Dialogues<S>,
+        handler: H,
+    ) -> &mut Self
+    where
+        S: Default + Send + 'static,
+        H: Fn(Api, Message, S) -> F + Send + Sync + 'static,
+        F: Future<Output = Result<Option<S>, Error>> + Send + 'static,
+    {
+        let handler = Arc::new(handler);
+        self.message(filter, move |api, message| {
+            let dialogues = dialogues.clone();
+            let handler = handler.clone();
+            async move {
+                let key = DialogueKey::from(&message);
+                dialogues
+                    .handle(key, |state| handler(api, message, state))
+                    .await
+            }
+        })
+    }
+
+    /// Register a handler for updates of any kind, useful as a fallback.
+    pub fn update<H, F>(&mut self, filter: Filter, handler: H) -> &mut Self
+    where
+        H: Fn(Api, Update) -> F + Send + Sync + 'static,
+        F: Future<Output = Result<(), Error>> + Send + 'static,
+    {
+        self.handlers.push((
+            filter,
+            Box::new(move |api, update| Some(handler(api, update).boxed())),
+        ));
+        self
+    }
+
+    fn register<T, H, F, E>(&mut self, filter: Filter, handler: H, extract: E) -> &mut Self
+    where
+        H: Fn(Api, T) -> F + Send + Sync + 'static,
+        F: Future<Output = Result<(), Error>> + Send + 'static,
+        E: Fn(UpdateKind) -> Option<T> + Send + Sync + 'static,
+    {
+        self.handlers.push((
+            filter,
+            Box::new(move |api, update| {
+                extract(update.kind).map(|value| handler(api, value).boxed())
+            }),
+        ));
+        self
+    }
+
+    /// Route the `update` to the first matching handler and wait until it's processed.
+    ///
+    /// Returns `false` if there is no matching handler.
+    pub async fn dispatch(&self, update: Update) -> Result<bool, Error> {
+        for (filter, handler) in &self.handlers {
+            if !filter.matches(&update) {
+                continue;
+            }
+
+            if let Some(future) = handler(self.api.clone(), update.clone()) {
+                future.await?;
+                return Ok(true);
+            }
+        }
+
+        tracing::trace!(update = ?update, "no matching handler");
+        Ok(false)
+    }
+
+    /// Dispatch all updates from the `stream` one by one until it ends.
+    ///
+    /// Errors returned by the stream or by handlers are logged and don't stop the processing.
+    pub async fn run<S>(&self, stream: S)
+    where
+        S: Stream<Item = Result<Update, Error>>,
+    {
+        futures::pin_mut!(stream);
+        while let Some(update) = stream.next().await {
+            let update = match update {
+                Ok(update) => update,
+                Err(error) => {
+                    tracing::error!(error = %error, "unable to receive update");
+                    continue;
+                }
+            };
+
+            let update_id = update.id;
+            if let Err(error) = self.dispatch(update).await {
+                tracing::error!(update_id = update_id, error = %error, "handler error");
+            }
+        }
+    }
+
+    /// Dispatch updates from the `stream` concurrently across chats using the `runner`,
+    /// see [`ChatRunner`](struct.ChatRunner.html) for the ordering guarantees.
+    pub async fn run_concurrently<S>(self, runner: &ChatRunner, stream: S)
+    where
+        S: Stream<Item = Result<Update, Error>>,
+    {
+        let dispatcher = Arc::new(self);
+        runner
+            .run(stream, move |update| {
+                let dispatcher = dispatcher.clone();
+                async move { dispatcher.dispatch(update).await.map(|_| ()) }
+            })
+            .await
+    }
+}
+
+impl fmt::Debug for Dispatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Dispatcher")
+            .field("handlers", &self.handlers.len())
+            .finish()
+    }
+}
+
+/// Condition on updates which selects a handler in the [`Dispatcher`](struct.Dispatcher.html).
+///
+/// Filters on the chat and text apply to messages and to callback queries
+/// with a message attached, text filters also match data of callback queries
+/// and queries of inline queries.
+#[derive(Clone)]
+pub struct Filter(Arc<dyn Fn(&Update) -> bool + Send + Sync>);
+
+impl Filter {
+    /// Create a new `Filter` from a custom predicate.
+    pub fn new<F>(predicate: F) -> Self
+    where
+        F: Fn(&Update) -> bool + Send + Sync + 'static,
+    {
+        Filter(Arc::new(predicate))
+    }
+
+    /// Filter which matches all updates.
+    pub fn any() -> Self {
+        Filter::new(|_| true)
+    }
+
+    /// Filter which matches updates from chats accepted by the `predicate`.
+    pub fn chat<F>(predicate: F) -> Self
+    where
+        F: Fn(&MessageChat) -> bool + Send + Sync + 'static,
+    {
+        Filter::new(move |update| match update_message(update) {
+            Some(message) => predicate(&message.chat),
+            None => false,
+        })
+    }
+
+    /// Filter which matches updates from private chats.
+    pub fn private() -> Self {
+        Filter::chat(|chat| matches!(chat, MessageChat::Private(_)))
+    }
+
+    /// Filter which matches updates from groups and supergroups.
+    pub fn group() -> Self {
+        Filter::chat(|chat| matches!(chat, MessageChat::Group(_) | MessageChat::Supergroup(_)))
+    }
+
+    /// Filter which matches updates with text matching the regular expression `pattern`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pattern` is not a valid regular expression.
+    pub fn text(pattern: &str) -> Self {
+        let regex = Regex::new(pattern).expect("invalid regular expression");
+        Filter::new(move |update| match update_text(update) {
+            Some(text) => regex.is_match(&text),
+            None => false,
+        })
+    }
+
+    /// Filter which matches messages with the bot command `name`, e.g. `/start`
+    /// or `/start@bot_name`. The leading slash in `name` is optional.
+    ///
+    /// Commands with the `@bot_name` suffix match only if it is the `username` of the bot,
+    /// see [`Command::parse`](struct.Command.html#method.parse).
+    pub fn command(name: &str, username: &str) -> Self {
+        let name = name.trim_start_matches('/').to_string();
+        let username = username.to_string();
+        Filter::new(move |update| match update_message(update) {
+            Some(message) => Command::parse(message, &username)
+                .filter(|command| command.name == name)
+                .is_some(),
+            None => false,
+        })
+    }
+
+    /// Filter which matches updates sent by the user with `user_id`.
+    pub fn user(user_id: UserId) -> Self {
+        Filter::new(move |update| match update_user(update) {
+            Some(user) => user.id == user_id,
+            None => false,
+        })
+    }
+
+    /// Filter which matches updates matched by both filters.
+    pub fn and(self, other: Filter) -> Self {
+        Filter::new(move |update| self.matches(update) && other.matches(update))
+    }
+
+    /// Filter which matches updates matched by any of the filters.
+    pub fn or(self, other: Filter) -> Self {
+        Filter::new(move |update| self.matches(update) || other.matches(update))
+    }
+
+    /// Returns `true` if the filter matches the `update`.
+    pub fn matches(&self, update: &Update) -> bool {
+        (self.0)(update)
+    }
+}
+
+impl Not for Filter {
+    type Output = Filter;
+
+    fn not(self) -> Filter {
+        Filter::new(move |update| !self.matches(update))
+    }
+}
+
+impl fmt::Debug for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Filter")
+    }
+}
+
+fn update_message(update: &Update) -> Option<&Message> {
+    match update.kind {
+        UpdateKind::Message(ref message) | UpdateKind::EditedMessage(ref message) => Some(message),
+        UpdateKind::CallbackQuery(CallbackQuery {
+            message: Some(MessageOrChannelPost::Message(ref message)),
+            ..
+        }) => Some(message),
+        _ => None,
+    }
+}
+
+fn update_user(update: &Update) -> Option<&User> {
+    match update.kind {
+        UpdateKind::Message(ref message) | UpdateKind::EditedMessage(ref message) => {
+            Some(&message.from)
+        }
+        UpdateKind::CallbackQuery(ref query) => Some(&query.from),
+        UpdateKind::InlineQuery(ref query) => Some(&query.from),
+        UpdateKind::PollAnswer(ref answer) => Some(&answer.user),
+        _ => None,
+    }
+}
+
+fn update_text(update: &Update) -> Option<String> {
+    match update.kind {
+        UpdateKind::Message(ref message) | UpdateKind::EditedMessage(ref message) => message.text(),
+        UpdateKind::CallbackQuery(ref query) => query.data.clone(),
+        UpdateKind::InlineQuery(ref query) => Some(query.query.clone()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use futures::stream;
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::connector::MockConnector;
+
+    fn update(value: Value) -> Update {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn message(update_id: i64, user_id: i64, chat: Value, text: &str) -> Update {
+        let mut entities = Vec::new();
+        if text.starts_with('/') {
+            let command = text.split_whitespace().next().unwrap_or_default();
+            entities.push(json!({"type": "bot_command", "offset": 0, "length": command.len()}));
+        }
+        update(json!({
+            "update_id": update_id,
+            "message": {
+                "message_id": update_id,
+                "date": 0,
+                "from": {"id": user_id, "is_bot": false, "first_name": "John"},
+                "chat": chat,
+                "text": text,
+                "entities": entities,
+            },
+        }))
+    }
+
+    #[test]
+    fn test_filters() {
+        let private = json!({"id": 42, "type": "private", "first_name": "John"});
+        let group = json!({
+            "id": -1,
+            "type": "group",
+            "title": "Group",
+            "all_members_are_administrators": false,
+        });
+
+        let start = message(1, 42, private.clone(), "/start now");
+        let command = |name, text| {
+            Filter::command(name, "bot_name").matches(&message(1, 42, group.clone(), text))
+        };
+        assert!(Filter::command("start", "bot_name").matches(&start));
+        assert!(Filter::command("/start", "bot_name").matches(&start));
+        assert!(!Filter::command("stop", "bot_name").matches(&start));
+        assert!(command("start", "/start@bot_name"));
+        assert!(command("start", "/start@Bot_Name now"));
+        assert!(!command("start", "/start@other_bot"));
+        assert!(!command("start", "/started"));
+        assert!(!command("start", "start"));
+
+        assert!(Filter::private().matches(&start));
+        assert!(!Filter::group().matches(&start));
+        assert!(Filter::group().matches(&message(1, 42, group, "text")));
+
+        assert!(Filter::text("^/st").matches(&start));
+        assert!(Filter::user(UserId::new(42)).matches(&start));
+        assert!((!Filter::user(UserId::new(43))).matches(&start));
+        assert!(Filter::private().and(Filter::text("now$")).matches(&start));
+        assert!(!Filter::group().and(Filter::any()).matches(&start));
+        assert!(Filter::group().or(Filter::any()).matches(&start));
+
+        let query = update(json!({
+            "update_id": 2,
+            "callback_query": {
+                "id": "1",
+                "from": {"id": 43, "is_bot": false, "first_name": "Jane"},
+                "chat_instance": "1",
+                "data": "vote:1",
+            },
+        }));
+        assert!(Filter::text("^vote:").matches(&query));
+        assert!(Filter::user(UserId::new(43)).matches(&query));
+        assert!(!Filter::private().matches(&query));
+    }
+
+    #[tokio::test]
+    async fn test_dispatch() {
+        let api = Api::with_connector("token", Box::new(MockConnector::new()));
+        let handled = Arc::new(Mutex::new(Vec::new()));
+        let private = json!({"id": 42, "type": "private", "first_name": "John"});
+
+        let mut dispatcher = Dispatcher::new(&api);
+        let log = handled.clone();
+        dispatcher.message(Filter::command("start", "bot_name"), move |_, message| {
+            log.lock().unwrap().push(format!("start {}", message.id));
+            async { Ok(()) }
+        });
+        let log = handled.clone();
+        dispatcher.message(Filter::any(), move |_, message| {
+            log.lock().unwrap().push(format!("message {}", message.id));
+            async { Ok(()) }
+        });
+        let log = handled.clone();
+        dispatcher.callback_query(Filter::any(), move |_, query| {
+            log.lock().unwrap().push(format!("query {:?}", query.data));
+            async { Ok(()) }
+        });
+
+        let query = update(json!({
+            "update_id": 3,
+            "callback_query": {
+                "id": "1",
+                "from": {"id": 43, "is_bot": false, "first_name": "Jane"},
+                "chat_instance": "1",
+                "data": "data",
+            },
+        }));
+        let poll_answer = update(json!({
+            "update_id": 4,
+            "poll_answer": {
+                "poll_id": "1",
+                "user": {"id": 43, "is_bot": false, "first_name": "Jane"},
+                "option_ids": [0],
+            },
+        }));
+
+        assert!(!dispatcher.dispatch(poll_answer).await.unwrap());
+        dispatcher
+            .run(stream::iter(vec![
+                Ok(message(1, 42, private.clone(), "/start")),
+                Ok(message(2, 42, private, "Hello!")),
+                Ok(query),
+            ]))
+            .await;
+
+        assert_eq!(
+            *handled.lock().unwrap(),
+            vec!["start 1", "message 2", "query Some(\"data\")"]
+        );
+    }
+}
@@ -1,7 +1,7 @@
 use std::error;
 use std::fmt;
 
-use telegram_bot_raw;
+use telegram_bot_raw::{self, ApiError, Integer, ResponseParameters};
 
 #[derive(Debug)]
 pub struct Error(ErrorKind);
@@ -12,7 +12,61 @@ pub(crate) enum ErrorKind {
     Hyper(hyper::Error),
     Http(hyper::http::Error),
     Io(std::io::Error),
+    Json(serde_json::Error),
     InvalidMultipartFilename,
+    InvalidProxy(String),
+    MissingFilePath,
+    UnsupportedDownload,
+    UnexpectedStatus(hyper::StatusCode),
+    UnexpectedRequest(String),
+    ShutdownTimeout(usize),
+    StreamShutdownTimeout,
+    PrefetchFailed(tokio::task::JoinError),
+    ReplyTimeout,
+    ReplyCancelled,
+    CallbackDataTooLong(usize),
+    InvalidCallbackData(String),
+}
+
+impl Error {
+    /// Classified error, if the request was rejected by Telegram.
+    pub fn api_error(&self) -> Option<ApiError> {
+        self.raw().and_then(|error| error.api_error())
+    }
+
+    /// Error code, if the request was rejected by Telegram.
+    pub fn error_code(&self) -> Option<Integer> {
+        self.raw().and_then(|error| error.error_code())
+    }
+
+    /// Human-readable description of the error, if the request was rejected by Telegram.
+    pub fn description(&self) -> Option<&str> {
+        self.raw().and_then(|error| error.description())
+    }
+
+    /// In case of exceeding flood control, the number of seconds left to wait
+    /// before the request can be repeated.
+    pub fn retry_after(&self) -> Option<Integer> {
+        self.parameters()
+            .and_then(|parameters| parameters.retry_after)
+    }
+
+    /// The group has been migrated to a supergroup with the specified identifier.
+    pub fn migrate_to_chat_id(&self) -> Option<Integer> {
+        self.parameters()
+            .and_then(|parameters| parameters.migrate_to_chat_id)
+    }
+
+    fn parameters(&self) -> Option<&ResponseParameters> {
+        self.raw().and_then(|error| error.parameters())
+    }
+
+    fn raw(&self) -> Option<&telegram_bot_raw::Error> {
+        match &self.0 {
+            ErrorKind::Raw(error) => Some(error),
+            _ => None,
+        }
+    }
 }
 
 impl From<telegram_bot_raw::Error> for ErrorKind {
@@ -39,6 +93,12 @@ impl From<std::io::Error> for ErrorKind {
     }
 }
 
+impl From<serde_json::Error> for ErrorKind {
+    fn from(error: serde_json::Error) -> Self {
+        ErrorKind::Json(error)
+    }
+}
+
 impl From<ErrorKind> for Error {
     fn from(kind: ErrorKind) -> Self {
         Error(kind)
@@ -52,7 +112,26 @@ impl fmt::Display for Error {
             ErrorKind::Hyper(error) => write!(f, "{}", error),
             ErrorKind::Http(error) => write!(f, "{}", error),
             ErrorKind::Io(error) => write!(f, "{}", error),
+            ErrorKind::Json(error) => write!(f, "{}", error),
             ErrorKind::InvalidMultipartFilename => write!(f, "invalid multipart filename"),
+            ErrorKind::InvalidProxy(proxy) => write!(f, "invalid proxy: {}", proxy),
+            ErrorKind::MissingFilePath => write!(f, "file path is not available"),
+            ErrorKind::UnsupportedDownload => write!(f, "connector doesn't support downloads"),
+            ErrorKind::UnexpectedStatus(status) => write!(f, "unexpected status code: {}", status),
+            ErrorKind::UnexpectedRequest(request) => write!(f, "unexpected request: {}", request),
+            ErrorKind::ShutdownTimeout(in_flight) => {
+                write!(f, "{} spawned requests are still in flight", in_flight)
+            }
+            ErrorKind::StreamShutdownTimeout => {
+                write!(f, "timed out waiting for the updates stream to stop")
+            }
+            ErrorKind::PrefetchFailed(error) => write!(f, "prefetch request failed: {}", error),
+            ErrorKind::ReplyTimeout => write!(f, "timed out waiting for a reply"),
+            ErrorKind::ReplyCancelled => write!(f, "waiting for a reply was cancelled"),
+            ErrorKind::CallbackDataTooLong(length) => {
+                write!(f, "callback data is too long: {} bytes", length)
+            }
+            ErrorKind::InvalidCallbackData(data) => write!(f, "invalid callback data: {:?}", data),
         }
     }
 }
@@ -2,17 +2,45 @@
 //! See [readme](https://github.com/telegram-rs/telegram-bot) for details.
 
 mod api;
+mod callback;
+mod command;
+mod dialogue;
+mod dispatcher;
 mod errors;
 mod macros;
+mod offset;
+mod reply;
+mod retry;
+mod runner;
+mod shutdown;
 mod stream;
+#[cfg(test)]
+mod test_util;
+mod webhook;
 
+#[cfg(feature = "blocking")]
+pub mod blocking;
 pub mod connector;
 pub mod prelude;
 pub mod types;
 pub mod util;
 
-pub use self::api::Api;
+pub use self::api::{Api, ApiBuilder};
+pub use self::callback::{CallbackCodec, CALLBACK_DATA_MAX_LENGTH};
+pub use self::command::{BotCommands, Command, CommandError};
+pub use self::dialogue::{
+    DialogueEntry, DialogueKey, DialogueStorage, Dialogues, FileDialogueStorage,
+    MemoryDialogueStorage,
+};
+pub use self::dispatcher::{Dispatcher, Filter};
 pub use self::errors::Error;
+pub use offset::{AckHandle, FileOffsetStore, MemoryOffsetStore, OffsetStore};
 pub use prelude::*;
+pub use reply::{Replies, Reply};
+pub use retry::{BackoffPolicy, RetryPolicy};
+pub use runner::ChatRunner;
+pub use shutdown::ShutdownHandle;
 pub use stream::UpdatesStream;
+pub use telegram_bot_derive::BotCommands;
 pub use types::*;
+pub use webhook::WebhookStream;
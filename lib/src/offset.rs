@@ -0,0 +1,276 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Waker};
+
+use telegram_bot_raw::{Integer, Update};
+
+use crate::errors::{Error, ErrorKind};
+
+/// Storage of the identifier of the last processed update, which allows
+/// `UpdatesStream` to continue from the same place after a restart.
+pub trait OffsetStore: Send + Sync {
+    /// Load identifier of the last processed update, `None` if nothing was saved yet.
+    fn load(&self) -> Result<Option<Integer>, Error>;
+
+    /// Save identifier of the last processed update.
+    ///
+    /// It is called by [`AckHandle::ack`](struct.AckHandle.html#method.ack) on the task
+    /// acknowledging the update, so a slow implementation stalls that task.
+    fn save(&self, update_id: Integer) -> Result<(), Error>;
+}
+
+/// Offset store which keeps the identifier in memory, clones share the same value.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryOffsetStore(Arc<Mutex<Option<Integer>>>);
+
+impl MemoryOffsetStore {
+    /// Create a new empty `MemoryOffsetStore`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl OffsetStore for MemoryOffsetStore {
+    fn load(&self) -> Result<Option<Integer>, Error> {
+        Ok(*self.0.lock().unwrap())
+    }
+
+    fn save(&self, update_id: Integer) -> Result<(), Error> {
+        *self.0.lock().unwrap() = Some(update_id);
+        Ok(())
+    }
+}
+
+/// Offset store which keeps the identifier in a text file.
+///
+/// The file is written to disk and replaced atomically, so it's never left half-written
+/// after a crash. Saving blocks the current thread until the data is synced to disk.
+#[derive(Debug, Clone)]
+pub struct FileOffsetStore {
+    path: PathBuf,
+}
+
+impl FileOffsetStore {
+    /// Create a new `FileOffsetStore` keeping the identifier in the file at `path`.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        FileOffsetStore {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl OffsetStore for FileOffsetStore {
+    fn load(&self) -> Result<Option<Integer>, Error> {
+        let data = match fs::read_to_string(&self.path) {
+            Ok(data) => data,
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(ErrorKind::from(error).into()),
+        };
+
+        match data.trim().parse() {
+            Ok(update_id) => Ok(Some(update_id)),
+            Err(error) => {
+                let error = io::Error::new(io::ErrorKind::InvalidData, error);
+                Err(ErrorKind::from(error).into())
+            }
+        }
+    }
+
+    fn save(&self, update_id: Integer) -> Result<(), Error> {
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+        let mut file = File::create(&temporary).map_err(ErrorKind::from)?;
+        file.write_all(update_id.to_string().as_bytes())
+            .map_err(ErrorKind::from)?;
+        file.sync_all().map_err(ErrorKind::from)?;
+        fs::rename(&temporary, &self.path).map_err(ErrorKind::from)?;
+        Ok(())
+    }
+}
+
+/// Handle for acknowledging updates received from the `UpdatesStream`
+/// which uses an `OffsetStore`, clones share the same state.
+///
+/// An update is committed to the store once it and all updates received before it
+/// are acknowledged. The stream doesn't request the next batch of updates until
+/// all received updates are acknowledged, so every update has to be acknowledged
+/// after it's processed.
+#[derive(Clone)]
+pub struct AckHandle(Arc<Mutex<Acknowledgements>>);
+
+struct Acknowledgements {
+    store: Box<dyn OffsetStore>,
+    committed: Option<Integer>,
+    pending: VecDeque<(Integer, bool)>,
+    waker: Option<Waker>,
+}
+
+impl AckHandle {
+    pub(crate) fn new(store: Box<dyn OffsetStore>) -> Self {
+        AckHandle(Arc::new(Mutex::new(Acknowledgements {
+            store,
+            committed: None,
+            pending: VecDeque::new(),
+            waker: None,
+        })))
+    }
+
+    /// Acknowledge that the `update` was processed.
+    pub fn ack(&self, update: &Update) -> Result<(), Error> {
+        self.ack_id(update.id)
+    }
+
+    /// Acknowledge that the update with `update_id` was processed.
+    pub fn ack_id(&self, update_id: Integer) -> Result<(), Error> {
+        let mut acks = self.0.lock().unwrap();
+        if let Some(entry) = acks.pending.iter_mut().find(|(id, _)| *id == update_id) {
+            entry.1 = true;
+        }
+
+        let mut committed = None;
+        while let Some(&(id, true)) = acks.pending.front() {
+            acks.pending.pop_front();
+            committed = Some(id);
+        }
+
+        if let Some(id) = committed {
+            tracing::trace!(update_id = id, "committing offset");
+            acks.store.save(id)?;
+            acks.committed = Some(id);
+        }
+
+        if acks.pending.is_empty() {
+            if let Some(waker) = acks.waker.take() {
+                waker.wake();
+            }
+        }
+        Ok(())
+    }
+
+    /// Register the update which was received but not processed yet.
+    pub(crate) fn receive(&self, update_id: Integer) {
+        self.0.lock().unwrap().pending.push_back((update_id, false));
+    }
+
+    /// Identifier of the last committed update.
+    pub(crate) fn committed(&self) -> Option<Integer> {
+        self.0.lock().unwrap().committed
+    }
+
+    /// Identifier of the last committed update, or `None` if some received updates
+    /// are not acknowledged yet, the current task is woken when they are.
+    pub(crate) fn poll_committed(&self, cx: &mut Context) -> Result<Option<Integer>, Error> {
+        let mut acks = self.0.lock().unwrap();
+        if !acks.pending.is_empty() {
+            acks.waker = Some(cx.waker().clone());
+            return Ok(None);
+        }
+
+        if acks.committed.is_none() {
+            acks.committed = Some(acks.store.load()?.unwrap_or(0));
+        }
+        Ok(acks.committed)
+    }
+}
+
+impl fmt::Debug for AckHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let acks = self.0.lock().unwrap();
+        f.debug_struct("AckHandle")
+            .field("committed", &acks.committed)
+            .field("pending", &acks.pending)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{FutureExt, StreamExt};
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::connector::MockConnector;
+    use crate::test_util::temp_path;
+    use crate::Api;
+
+    fn update(update_id: Integer) -> Value {
+        json!({
+            "update_id": update_id,
+            "message": {
+                "message_id": update_id,
+                "date": 0,
+                "from": {"id": 42, "is_bot": false, "first_name": "John"},
+                "chat": {"id": 42, "type": "private", "first_name": "John"},
+                "text": "Hello!",
+            },
+        })
+    }
+
+    #[test]
+    fn test_file_offset_store() {
+        let path = temp_path("offset");
+
+        let store = FileOffsetStore::new(&path);
+        assert_eq!(store.load().unwrap(), None);
+        store.save(42).unwrap();
+        assert_eq!(store.load().unwrap(), Some(42));
+        assert_eq!(FileOffsetStore::new(&path).load().unwrap(), Some(42));
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_ack() {
+        let store = MemoryOffsetStore::new();
+        let acks = AckHandle::new(Box::new(store.clone()));
+        acks.receive(10);
+        acks.receive(11);
+        acks.receive(12);
+
+        acks.ack_id(11).unwrap();
+        assert_eq!(store.load().unwrap(), None);
+        acks.ack_id(10).unwrap();
+        assert_eq!(store.load().unwrap(), Some(11));
+        acks.ack_id(12).unwrap();
+        assert_eq!(store.load().unwrap(), Some(12));
+    }
+
+    #[tokio::test]
+    async fn test_stream_acks() {
+        let store = MemoryOffsetStore::new();
+        store.save(9).unwrap();
+
+        let mock = MockConnector::new();
+        mock.on("getUpdates", |request| {
+            match request.params["offset"].as_i64() {
+                Some(10) => MockConnector::ok(json!([update(10), update(11)])),
+                Some(12) => MockConnector::ok(json!([update(12)])),
+                offset => panic!("unexpected offset {:?}", offset),
+            }
+        });
+
+        let api = Api::with_connector("token", Box::new(mock.clone()));
+        let mut stream = api.stream();
+        stream.offset_store(store.clone());
+        let acks = stream.ack_handle().unwrap();
+
+        let first = stream.next().await.unwrap().unwrap();
+        let second = stream.next().await.unwrap().unwrap();
+        assert_eq!((first.id, second.id), (10, 11));
+        assert!(stream.next().now_or_never().is_none());
+        assert_eq!(mock.requests_to("getUpdates").len(), 1);
+
+        acks.ack(&second).unwrap();
+        assert!(stream.next().now_or_never().is_none());
+        assert_eq!(store.load().unwrap(), Some(9));
+
+        acks.ack(&first).unwrap();
+        assert_eq!(store.load().unwrap(), Some(11));
+        assert_eq!(stream.next().await.unwrap().unwrap().id, 12);
+        assert_eq!(mock.requests_to("getUpdates").len(), 2);
+    }
+}
@@ -27,4 +27,4 @@ pub use telegram_bot_raw::{CanReplySendVideo, CanSendVideo};
 pub use telegram_bot_raw::{CanUnbanChatMemberForChat, CanUnbanChatMemberForUser};
 pub use telegram_bot_raw::{ToReplyRequest, ToRequest};
 
-pub use crate::util::messages::{MessageGetFiles, MessageText};
+pub use crate::util::messages::{MessageGetFiles, MessageRichText, MessageText};
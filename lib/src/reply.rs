@@ -0,0 +1,345 @@
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::channel::oneshot;
+use futures::{future, FutureExt, Stream, StreamExt};
+
+use telegram_bot_raw::{
+    CallbackQuery, ChatId, Message, MessageId, MessageOrChannelPost, Request, Update, UpdateKind,
+};
+
+use crate::api::Api;
+use crate::dialogue::DialogueKey;
+use crate::errors::{Error, ErrorKind};
+
+/// Registry of handlers waiting for replies, clones share the same registry.
+///
+/// Updates have to pass through [`intercept`](#method.intercept) before they reach
+/// the normal handlers: an update is delivered to the first waiting handler it matches,
+/// all other updates flow to the normal handlers.
+///
+/// Handlers waiting for replies must run concurrently with the stream consumption,
+/// e.g. using the [`ChatRunner`](struct.ChatRunner.html), otherwise they wait forever.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+/// use telegram_bot::{Api, CanReplySendMessage, ChatRunner, DialogueKey, Dispatcher, Filter};
+/// use telegram_bot::{MessageText, Replies};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// # let api: Api = Api::new("token");
+/// let replies = Replies::new();
+///
+/// let mut dispatcher = Dispatcher::new(&api);
+/// let handler_replies = replies.clone();
+/// dispatcher.message(Filter::command("start", "bot_name"), move |api, message| {
+///     let replies = handler_replies.clone();
+///     async move {
+///         let question = message.text_reply("What's your name?");
+///         let key = DialogueKey::from(&message);
+///         let answer = replies.ask(&api, key, question, Duration::from_secs(60)).await?;
+///         let greeting = format!("Hello, {}!", answer.text().unwrap_or_default());
+///         api.send(answer.text_reply(greeting)).await?;
+///         Ok(())
+///     }
+/// });
+///
+/// # if false {
+/// let stream = replies.intercept(api.stream());
+/// dispatcher.run_concurrently(&ChatRunner::new(), stream).await;
+/// # }
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct Replies {
+    waiters: Arc<Mutex<Waiters>>,
+}
+
+#[derive(Default)]
+struct Waiters {
+    next_id: usize,
+    messages: Vec<(usize, DialogueKey, oneshot::Sender<Message>)>,
+    callback_queries: Vec<(usize, (ChatId, MessageId), oneshot::Sender<CallbackQuery>)>,
+}
+
+impl Replies {
+    /// Create a new empty `Replies` registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wait for the next message from the user in the chat identified by `key`.
+    pub fn message(&self, key: DialogueKey) -> Reply<Message> {
+        let (sender, receiver) = oneshot::channel();
+        let mut waiters = self.waiters.lock().unwrap();
+        let id = waiters.next_id();
+        waiters.messages.push((id, key, sender));
+        self.reply(id, receiver)
+    }
+
+    /// Wait for the next callback query on the message with `message_id` in the chat.
+    pub fn callback_query(&self, chat_id: ChatId, message_id: MessageId) -> Reply<CallbackQuery> {
+        let (sender, receiver) = oneshot::channel();
+        let mut waiters = self.waiters.lock().unwrap();
+        let id = waiters.next_id();
+        waiters
+            .callback_queries
+            .push((id, (chat_id, message_id), sender));
+        self.reply(id, receiver)
+    }
+
+    /// Send the `request` and wait at most `timeout` for the next message
+    /// from the user in the chat identified by `key`.
+    pub async fn ask<Req: Request>(
+        &self,
+        api: &Api,
+        key: DialogueKey,
+        request: Req,
+        timeout: Duration,
+    ) -> Result<Message, Error> {
+        // Register before sending, so a quick answer isn't missed.
+        let reply = self.message(key);
+        api.send(request).await?;
+        reply.timeout(timeout).await
+    }
+
+    /// Cancel all waits for messages from the user in the chat identified by `key`.
+    pub fn cancel(&self, key: DialogueKey) {
+        self.waiters
+            .lock()
+            .unwrap()
+            .messages
+            .retain(|(_, waiter_key, _)| *waiter_key != key);
+    }
+
+    /// Deliver the `update` to a matching waiting handler. Returns the update back
+    /// if there is no such handler.
+    pub fn offer(&self, update: Update) -> Option<Update> {
+        let mut waiters = self.waiters.lock().unwrap();
+        let Update { id, kind } = update;
+
+        let kind = match kind {
+            UpdateKind::Message(message) => {
+                let key = DialogueKey::from(&message);
+                match waiters.messages.iter().position(|waiter| waiter.1 == key) {
+                    Some(position) => {
+                        let (_, _, sender) = waiters.messages.remove(position);
+                        match sender.send(message) {
+                            Ok(()) => return None,
+                            Err(message) => UpdateKind::Message(message),
+                        }
+                    }
+                    None => UpdateKind::Message(message),
+                }
+            }
+            UpdateKind::CallbackQuery(query) => {
+                let target = match query.message {
+                    Some(MessageOrChannelPost::Message(ref message)) => {
+                        Some((message.chat.id(), message.id))
+                    }
+                    Some(MessageOrChannelPost::ChannelPost(ref post)) => {
+                        Some((post.chat.id.into(), post.id))
+                    }
+                    None => None,
+                };
+                let position = waiters
+                    .callback_queries
+                    .iter()
+                    .position(|waiter| Some(waiter.1) == target);
+                match position {
+                    Some(position) => {
+                        let (_, _, sender) = waiters.callback_queries.remove(position);
+                        match sender.send(query) {
+                            Ok(()) => return None,
+                            Err(query) => UpdateKind::CallbackQuery(query),
+                        }
+                    }
+                    None => UpdateKind::CallbackQuery(query),
+                }
+            }
+            kind => kind,
+        };
+
+        Some(Update { id, kind })
+    }
+
+    /// Pass updates from the `stream` through the registry,
+    /// the resulting stream contains only updates no handler was waiting for.
+    pub fn intercept<S>(&self, stream: S) -> impl Stream<Item = Result<Update, Error>>
+    where
+        S: Stream<Item = Result<Update, Error>>,
+    {
+        let replies = self.clone();
+        stream.filter_map(move |update| {
+            future::ready(match update {
+                Ok(update) => replies.offer(update).map(Ok),
+                Err(error) => Some(Err(error)),
+            })
+        })
+    }
+
+    fn reply<T>(&self, id: usize, receiver: oneshot::Receiver<T>) -> Reply<T> {
+        Reply {
+            id,
+            receiver,
+            waiters: self.waiters.clone(),
+        }
+    }
+}
+
+impl fmt::Debug for Replies {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let waiters = self.waiters.lock().unwrap();
+        f.debug_struct("Replies")
+            .field("messages", &waiters.messages.len())
+            .field("callback_queries", &waiters.callback_queries.len())
+            .finish()
+    }
+}
+
+impl Waiters {
+    fn next_id(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id
+    }
+}
+
+/// Future resolving with the reply a handler is waiting for, created by [`Replies`](struct.Replies.html).
+///
+/// Dropping the future cancels the wait.
+#[must_use = "futures do nothing unless polled"]
+pub struct Reply<T> {
+    id: usize,
+    receiver: oneshot::Receiver<T>,
+    waiters: Arc<Mutex<Waiters>>,
+}
+
+impl<T> Reply<T> {
+    /// Wait for the reply at most for `duration`.
+    pub async fn timeout(self, duration: Duration) -> Result<T, Error> {
+        match tokio::time::timeout(duration, self).await {
+            Ok(result) => result,
+            Err(_) => Err(ErrorKind::ReplyTimeout.into()),
+        }
+    }
+}
+
+impl<T> Future for Reply<T> {
+    type Output = Result<T, Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.receiver
+            .poll_unpin(cx)
+            .map(|result| result.map_err(|_| ErrorKind::ReplyCancelled.into()))
+    }
+}
+
+impl<T> Drop for Reply<T> {
+    fn drop(&mut self) {
+        let id = self.id;
+        let mut waiters = self.waiters.lock().unwrap();
+        waiters.messages.retain(|waiter| waiter.0 != id);
+        waiters.callback_queries.retain(|waiter| waiter.0 != id);
+    }
+}
+
+impl<T> fmt::Debug for Reply<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reply").field("id", &self.id).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::stream;
+    use serde_json::{json, Value};
+    use telegram_bot_raw::UserId;
+
+    use super::*;
+
+    fn update(value: Value) -> Update {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn message(update_id: i64, user_id: i64) -> Update {
+        update(json!({
+            "update_id": update_id,
+            "message": {
+                "message_id": update_id,
+                "date": 0,
+                "from": {"id": user_id, "is_bot": false, "first_name": "John"},
+                "chat": {"id": 42, "type": "private", "first_name": "John"},
+                "text": "Hello!",
+            },
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_message() {
+        let replies = Replies::new();
+        let reply = replies.message(DialogueKey::new(ChatId::new(42), UserId::new(1)));
+
+        let updates = vec![Ok(message(1, 2)), Ok(message(2, 1)), Ok(message(3, 1))];
+        let passed: Vec<_> = replies
+            .intercept(stream::iter(updates))
+            .map(|update| update.unwrap().id)
+            .collect()
+            .await;
+
+        assert_eq!(passed, vec![1, 3]);
+        assert_eq!(reply.await.unwrap().id, MessageId::new(2));
+    }
+
+    #[tokio::test]
+    async fn test_callback_query() {
+        let replies = Replies::new();
+        let reply = replies.callback_query(ChatId::new(42), MessageId::new(7));
+
+        let query = update(json!({
+            "update_id": 1,
+            "callback_query": {
+                "id": "1",
+                "from": {"id": 1, "is_bot": false, "first_name": "John"},
+                "chat_instance": "1",
+                "data": "yes",
+                "message": {
+                    "message_id": 7,
+                    "date": 0,
+                    "from": {"id": 2, "is_bot": true, "first_name": "Bot"},
+                    "chat": {"id": 42, "type": "private", "first_name": "John"},
+                    "text": "Are you sure?",
+                },
+            },
+        }));
+        assert!(replies.offer(query).is_none());
+        assert_eq!(reply.await.unwrap().data, Some("yes".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_cancellation() {
+        let replies = Replies::new();
+        let key = DialogueKey::new(ChatId::new(42), UserId::new(1));
+
+        let reply = replies.message(key);
+        replies.cancel(key);
+        assert!(reply.await.is_err());
+
+        drop(replies.message(key));
+        assert!(replies.offer(message(1, 1)).is_some());
+
+        let error = replies
+            .message(key)
+            .timeout(Duration::from_millis(10))
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "timed out waiting for a reply");
+        assert!(replies.offer(message(2, 1)).is_some());
+    }
+}
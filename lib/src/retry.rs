@@ -0,0 +1,245 @@
+use std::cmp::{max, min};
+use std::time::Duration;
+
+use crate::errors::Error;
+
+const RETRY_DEFAULT_MAX_ATTEMPTS: usize = 3;
+const RETRY_DEFAULT_BACKOFF_MILLISECONDS: u64 = 1000;
+const RETRY_DEFAULT_MAX_BACKOFF_SECONDS: u64 = 60;
+const BACKOFF_DEFAULT_INITIAL_DELAY_MILLISECONDS: u64 = 500;
+const BACKOFF_DEFAULT_MAX_DELAY_SECONDS: u64 = 30;
+const BACKOFF_DEFAULT_MULTIPLIER: u32 = 2;
+
+/// Policy of retrying requests rejected by Telegram with
+/// [`ResponseParameters`](../telegram_bot_raw/types/response_parameters/struct.ResponseParameters.html).
+///
+/// Requests which exceeded flood control are retried after `retry_after` seconds,
+/// requests to groups migrated to a supergroup are immediately retried with the new chat id.
+/// Other errors are never retried.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+/// use telegram_bot::{Api, RetryPolicy};
+///
+/// # let telegram_token = "token";
+/// let mut policy = RetryPolicy::new();
+/// policy.max_attempts(5).backoff(Duration::from_secs(1), Duration::from_secs(30));
+///
+/// let api = Api::builder(telegram_token).retry_policy(policy).build();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    max_attempts: usize,
+    backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Create a new `RetryPolicy` instance.
+    pub fn new() -> Self {
+        RetryPolicy {
+            max_attempts: RETRY_DEFAULT_MAX_ATTEMPTS,
+            backoff: Duration::from_millis(RETRY_DEFAULT_BACKOFF_MILLISECONDS),
+            max_backoff: Duration::from_secs(RETRY_DEFAULT_MAX_BACKOFF_SECONDS),
+        }
+    }
+
+    /// Set the maximum number of attempts to send a request, including the first one.
+    ///
+    /// Default is 3 attempts.
+    pub fn max_attempts(&mut self, max_attempts: usize) -> &mut Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Set the minimal delay before retrying a request which exceeded flood control.
+    /// The delay is doubled after every attempt up to `max_backoff`, but is never
+    /// shorter than `retry_after` requested by Telegram.
+    ///
+    /// Default backoff is 1 second, limited by 60 seconds.
+    pub fn backoff(&mut self, backoff: Duration, max_backoff: Duration) -> &mut Self {
+        self.backoff = backoff;
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Returns a delay before the next attempt or `None` if the request should not be retried.
+    pub(crate) fn delay(&self, attempt: usize, error: &Error) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+
+        if let Some(seconds) = error.retry_after() {
+            let exponent = min(attempt.saturating_sub(1), 31) as u32;
+            let backoff = min(
+                self.backoff
+                    .checked_mul(1 << exponent)
+                    .unwrap_or(self.max_backoff),
+                self.max_backoff,
+            );
+            return Some(max(Duration::from_secs(max(seconds, 0) as u64), backoff));
+        }
+
+        if error.migrate_to_chat_id().is_some() {
+            return Some(Duration::from_secs(0));
+        }
+
+        None
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Policy of delaying requests after consecutive errors, used by
+/// [`UpdatesStream`](struct.UpdatesStream.html) to avoid busy looping
+/// when Telegram or the network is unavailable.
+///
+/// The first request after an error is delayed by `initial_delay`, every next
+/// consecutive error multiplies the delay by `multiplier` up to `max_delay`.
+/// The delay is reset after a successful request.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+/// use telegram_bot::{Api, BackoffPolicy};
+///
+/// # let telegram_token = "token";
+/// # let api = Api::new(telegram_token);
+/// let mut policy = BackoffPolicy::new();
+/// policy
+///     .initial_delay(Duration::from_secs(1))
+///     .max_delay(Duration::from_secs(60))
+///     .multiplier(3);
+///
+/// let mut stream = api.stream();
+/// stream.backoff(policy);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackoffPolicy {
+    initial_delay: Duration,
+    max_delay: Duration,
+    multiplier: u32,
+}
+
+impl BackoffPolicy {
+    /// Create a new `BackoffPolicy` instance.
+    pub fn new() -> Self {
+        BackoffPolicy {
+            initial_delay: Duration::from_millis(BACKOFF_DEFAULT_INITIAL_DELAY_MILLISECONDS),
+            max_delay: Duration::from_secs(BACKOFF_DEFAULT_MAX_DELAY_SECONDS),
+            multiplier: BACKOFF_DEFAULT_MULTIPLIER,
+        }
+    }
+
+    /// Set the delay after the first error.
+    ///
+    /// Default delay is 500 ms.
+    pub fn initial_delay(&mut self, delay: Duration) -> &mut Self {
+        self.initial_delay = delay;
+        self
+    }
+
+    /// Set the upper limit of the delay.
+    ///
+    /// Default limit is 30 seconds.
+    pub fn max_delay(&mut self, delay: Duration) -> &mut Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Set the factor the delay is multiplied by after every consecutive error,
+    /// `1` keeps the delay constant.
+    ///
+    /// Default multiplier is 2.
+    pub fn multiplier(&mut self, multiplier: u32) -> &mut Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    /// Returns a delay before the next request after `errors` consecutive errors.
+    pub(crate) fn delay(&self, errors: usize) -> Duration {
+        if errors == 0 {
+            return Duration::from_secs(0);
+        }
+
+        let mut delay = self.initial_delay;
+        for _ in 1..errors {
+            delay = match delay.checked_mul(self.multiplier) {
+                Some(delay) if delay < self.max_delay => delay,
+                _ => return self.max_delay,
+            };
+        }
+        min(delay, self.max_delay)
+    }
+}
+
+impl Default for BackoffPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use telegram_bot_raw::{HttpResponse, JsonIdResponse, ResponseType, True};
+
+    use super::*;
+    use crate::errors::ErrorKind;
+
+    fn error(body: &str) -> Error {
+        let response = HttpResponse {
+            status: 400,
+            headers: Vec::new(),
+            body: Some(body.as_bytes().to_vec()),
+        };
+        let error = <JsonIdResponse<True>>::deserialize(response).unwrap_err();
+        ErrorKind::from(error).into()
+    }
+
+    #[test]
+    fn test_delay() {
+        let mut policy = RetryPolicy::new();
+        policy
+            .max_attempts(4)
+            .backoff(Duration::from_secs(2), Duration::from_secs(5));
+
+        let flood = error(
+            r#"{"ok":false,"error_code":429,"description":"Too Many Requests: retry after 3","parameters":{"retry_after":3}}"#,
+        );
+        assert_eq!(policy.delay(1, &flood), Some(Duration::from_secs(3)));
+        assert_eq!(policy.delay(2, &flood), Some(Duration::from_secs(4)));
+        assert_eq!(policy.delay(3, &flood), Some(Duration::from_secs(5)));
+        assert_eq!(policy.delay(4, &flood), None);
+
+        let migrated = error(
+            r#"{"ok":false,"error_code":400,"description":"Bad Request: group chat was upgraded to a supergroup chat","parameters":{"migrate_to_chat_id":-1001234}}"#,
+        );
+        assert_eq!(policy.delay(1, &migrated), Some(Duration::from_secs(0)));
+
+        let other = error(r#"{"ok":false,"error_code":400,"description":"Bad Request"}"#);
+        assert_eq!(policy.delay(1, &other), None);
+    }
+
+    #[test]
+    fn test_backoff() {
+        let mut policy = BackoffPolicy::new();
+        policy
+            .initial_delay(Duration::from_secs(1))
+            .max_delay(Duration::from_secs(10))
+            .multiplier(3);
+
+        assert_eq!(policy.delay(0), Duration::from_secs(0));
+        assert_eq!(policy.delay(1), Duration::from_secs(1));
+        assert_eq!(policy.delay(2), Duration::from_secs(3));
+        assert_eq!(policy.delay(3), Duration::from_secs(9));
+        assert_eq!(policy.delay(4), Duration::from_secs(10));
+        assert_eq!(policy.delay(1000), Duration::from_secs(10));
+    }
+}
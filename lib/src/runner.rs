@@ -0,0 +1,424 @@
+use std::cmp::max;
+use std::collections::HashMap;
+use std::future::Future;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::channel::mpsc;
+use futures::{FutureExt, Stream, StreamExt};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::timeout;
+
+use telegram_bot_raw::{ChatId, MessageOrChannelPost, Update, UpdateKind};
+
+use crate::errors::Error;
+
+const RUNNER_DEFAULT_CONCURRENCY: usize = 64;
+const RUNNER_DEFAULT_QUEUE_LIMIT: usize = 64;
+const RUNNER_DEFAULT_IDLE_TIMEOUT_SECONDS: u64 = 60;
+
+type Workers = Arc<Mutex<HashMap<ChatId, Worker>>>;
+
+/// Queue of a chat served by a worker task.
+struct Worker {
+    sender: mpsc::UnboundedSender<(Update, OwnedSemaphorePermit)>,
+    /// Free places in the queue.
+    places: Arc<Semaphore>,
+}
+
+/// Runner which processes updates concurrently across chats, but serially within a chat.
+///
+/// Updates are grouped by the chat of the message, the channel post or the message
+/// of the callback query, and every chat is served by a separate worker task
+/// in the order in which updates were received. Updates without a chat,
+/// e.g. inline queries or polls, are processed in separate tasks without any ordering.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+/// use telegram_bot::{Api, CanReplySendMessage, ChatRunner, UpdateKind};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// # let api: Api = Api::new("token");
+/// let mut runner = ChatRunner::new();
+/// runner
+///     .concurrency(16)
+///     .queue_limit(32)
+///     .idle_timeout(Duration::from_secs(30));
+///
+/// # if false {
+/// let handler_api = api.clone();
+/// runner
+///     .run(api.stream(), move |update| {
+///         let api = handler_api.clone();
+///         async move {
+///             if let UpdateKind::Message(message) = update.kind {
+///                 api.send(message.text_reply("Received")).await?;
+///             }
+///             Ok(())
+///         }
+///     })
+///     .await;
+/// # }
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ChatRunner {
+    concurrency: usize,
+    queue_limit: usize,
+    idle_timeout: Duration,
+}
+
+impl ChatRunner {
+    /// Create a new `ChatRunner` instance.
+    pub fn new() -> Self {
+        ChatRunner {
+            concurrency: RUNNER_DEFAULT_CONCURRENCY,
+            queue_limit: RUNNER_DEFAULT_QUEUE_LIMIT,
+            idle_timeout: Duration::from_secs(RUNNER_DEFAULT_IDLE_TIMEOUT_SECONDS),
+        }
+    }
+
+    /// Set the maximum number of updates which are processed at the same time.
+    /// The limit is at least 1.
+    ///
+    /// Default limit is 64 updates.
+    pub fn concurrency(&mut self, concurrency: usize) -> &mut Self {
+        self.concurrency = max(concurrency, 1);
+        self
+    }
+
+    /// Set the maximum number of updates which are queued for a chat, the stream
+    /// isn't polled while the queue of a chat is full. The limit is at least 1.
+    ///
+    /// Default limit is 64 updates.
+    pub fn queue_limit(&mut self, queue_limit: usize) -> &mut Self {
+        self.queue_limit = max(queue_limit, 1);
+        self
+    }
+
+    /// Set the time after which a worker of a chat without new updates is stopped.
+    ///
+    /// Default timeout is 60 seconds.
+    pub fn idle_timeout(&mut self, idle_timeout: Duration) -> &mut Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Process all updates from the `stream` with the `handler` until the stream ends,
+    /// and wait until all received updates are processed.
+    ///
+    /// Errors returned by the stream or by the handler, as well as panics of the handler,
+    /// are logged and don't stop the processing.
+    pub async fn run<S, H, F>(&self, stream: S, handler: H)
+    where
+        S: Stream<Item = Result<Update, Error>>,
+        H: Fn(Update) -> F + Send + Sync + 'static,
+        F: Future<Output = Result<(), Error>> + Send + 'static,
+    {
+        let handler = Arc::new(handler);
+        let semaphore = Arc::new(Semaphore::new(self.concurrency));
+        let workers: Workers = Default::default();
+        // Every task holds a clone of the sender, the receiver ends once all of them finished.
+        let (running, mut finished) = mpsc::unbounded::<()>();
+
+        let start_worker = |chat_id: ChatId| {
+            tracing::trace!(chat_id = %chat_id, "starting worker");
+            let (sender, receiver) = mpsc::unbounded();
+            tokio::spawn(worker(
+                chat_id,
+                receiver,
+                workers.clone(),
+                handler.clone(),
+                semaphore.clone(),
+                self.idle_timeout,
+                running.clone(),
+            ));
+            Worker {
+                sender,
+                places: Arc::new(Semaphore::new(self.queue_limit)),
+            }
+        };
+
+        futures::pin_mut!(stream);
+        while let Some(update) = stream.next().await {
+            let update = match update {
+                Ok(update) => update,
+                Err(error) => {
+                    tracing::error!(error = %error, "unable to receive update");
+                    continue;
+                }
+            };
+
+            let chat_id = match update_chat_id(&update) {
+                Some(chat_id) => chat_id,
+                None => {
+                    let permit = semaphore.clone().acquire_owned().await;
+                    let handler = handler.clone();
+                    let running = running.clone();
+                    tokio::spawn(async move {
+                        process(&*handler, update).await;
+                        drop(permit);
+                        drop(running);
+                    });
+                    continue;
+                }
+            };
+
+            let places = workers
+                .lock()
+                .unwrap()
+                .entry(chat_id)
+                .or_insert_with(|| start_worker(chat_id))
+                .places
+                .clone();
+            let place = places.acquire_owned().await;
+
+            // Updates are queued under the lock, so the worker can't stop in between.
+            let mut chats = workers.lock().unwrap();
+            let chat = chats
+                .entry(chat_id)
+                .or_insert_with(|| start_worker(chat_id));
+            if let Err(error) = chat.sender.unbounded_send((update, place)) {
+                // The worker is gone, replace it with a new one.
+                tracing::error!(chat_id = %chat_id, "worker stopped unexpectedly");
+                let chat = start_worker(chat_id);
+                let _ = chat.sender.unbounded_send(error.into_inner());
+                chats.insert(chat_id, chat);
+            }
+        }
+
+        // Stop idle workers and wait until all updates are processed.
+        workers.lock().unwrap().clear();
+        drop(running);
+        while finished.next().await.is_some() {}
+    }
+}
+
+impl Default for ChatRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+async fn worker<H, F>(
+    chat_id: ChatId,
+    mut receiver: mpsc::UnboundedReceiver<(Update, OwnedSemaphorePermit)>,
+    workers: Workers,
+    handler: Arc<H>,
+    semaphore: Arc<Semaphore>,
+    idle_timeout: Duration,
+    running: mpsc::UnboundedSender<()>,
+) where
+    H: Fn(Update) -> F,
+    F: Future<Output = Result<(), Error>>,
+{
+    loop {
+        let (update, place) = match timeout(idle_timeout, receiver.next()).await {
+            Ok(Some(item)) => item,
+            Ok(None) => break,
+            Err(_) => {
+                // Check the queue under the lock, so no update is sent to a stopped worker.
+                let mut workers = workers.lock().unwrap();
+                match receiver.try_recv() {
+                    Ok(item) => item,
+                    Err(_) => {
+                        workers.remove(&chat_id);
+                        break;
+                    }
+                }
+            }
+        };
+
+        let permit = semaphore.acquire().await;
+        process(&*handler, update).await;
+        drop(permit);
+        drop(place);
+    }
+    drop(running);
+    tracing::trace!(chat_id = %chat_id, "worker stopped");
+}
+
+async fn process<H, F>(handler: &H, update: Update)
+where
+    H: Fn(Update) -> F,
+    F: Future<Output = Result<(), Error>>,
+{
+    let update_id = update.id;
+    // A panicking handler must not stop the worker, neither when it is called
+    // nor when its future is polled.
+    let result = match catch_unwind(AssertUnwindSafe(|| handler(update))) {
+        Ok(future) => AssertUnwindSafe(future).catch_unwind().await,
+        Err(panic) => Err(panic),
+    };
+    match result {
+        Ok(Ok(())) => (),
+        Ok(Err(error)) => {
+            tracing::error!(update_id = update_id, error = %error, "handler error")
+        }
+        Err(_) => tracing::error!(update_id = update_id, "handler panicked"),
+    }
+}
+
+/// Chat in which the update happened, if any.
+fn update_chat_id(update: &Update) -> Option<ChatId> {
+    match update.kind {
+        UpdateKind::Message(ref message) | UpdateKind::EditedMessage(ref message) => {
+            Some(message.chat.id())
+        }
+        UpdateKind::ChannelPost(ref post) | UpdateKind::EditedChannelPost(ref post) => {
+            Some(post.chat.id.into())
+        }
+        UpdateKind::CallbackQuery(ref query) => match query.message {
+            Some(MessageOrChannelPost::Message(ref message)) => Some(message.chat.id()),
+            Some(MessageOrChannelPost::ChannelPost(ref post)) => Some(post.chat.id.into()),
+            None => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::stream;
+    use serde_json::json;
+    use tokio::time::delay_for;
+
+    use super::*;
+
+    fn message(update_id: i64, chat_id: i64) -> Result<Update, Error> {
+        Ok(serde_json::from_value(json!({
+            "update_id": update_id,
+            "message": {
+                "message_id": update_id,
+                "date": 0,
+                "from": {"id": chat_id, "is_bot": false, "first_name": "John"},
+                "chat": {"id": chat_id, "type": "private", "first_name": "John"},
+                "text": "Hello!",
+            },
+        }))
+        .unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_run() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let handler_log = log.clone();
+
+        let mut runner = ChatRunner::new();
+        runner
+            .concurrency(3)
+            .idle_timeout(Duration::from_millis(10));
+        let updates = vec![
+            message(1, 1),
+            message(2, 1),
+            message(3, 2),
+            message(4, 1),
+            message(5, 2),
+        ];
+        runner
+            .run(stream::iter(updates), move |update| {
+                let log = handler_log.clone();
+                async move {
+                    // The first update of every chat is the slowest one.
+                    let delay = if update.id < 4 {
+                        40 - update.id * 10
+                    } else {
+                        0
+                    };
+                    delay_for(Duration::from_millis(delay as u64)).await;
+                    log.lock().unwrap().push(update.id);
+                    Ok(())
+                }
+            })
+            .await;
+
+        let log = log.lock().unwrap();
+        assert_eq!(log.len(), 5);
+        let position = |id| log.iter().position(|&x| x == id).unwrap();
+        assert!(position(1) < position(2) && position(2) < position(4));
+        assert!(position(3) < position(5));
+        assert!(position(3) < position(1));
+    }
+
+    #[tokio::test]
+    async fn test_panic() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let handler_log = log.clone();
+
+        let mut runner = ChatRunner::new();
+        runner.concurrency(0);
+        let updates = vec![message(1, 1), message(2, 1), message(3, 2)];
+        runner
+            .run(stream::iter(updates), move |update| {
+                let log = handler_log.clone();
+                async move {
+                    if update.id == 1 {
+                        panic!("handler panicked");
+                    }
+                    log.lock().unwrap().push(update.id);
+                    Ok(())
+                }
+            })
+            .await;
+
+        assert_eq!(*log.lock().unwrap(), vec![2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_panic_on_call() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let handler_log = log.clone();
+
+        let runner = ChatRunner::new();
+        let updates = vec![message(1, 1), message(2, 1), message(3, 2)];
+        runner
+            .run(stream::iter(updates), move |update| {
+                if update.id == 1 {
+                    panic!("handler panicked");
+                }
+                let log = handler_log.clone();
+                async move {
+                    log.lock().unwrap().push(update.id);
+                    Ok(())
+                }
+            })
+            .await;
+
+        assert_eq!(*log.lock().unwrap(), vec![2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_busy_chat() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let handler_log = log.clone();
+
+        let mut runner = ChatRunner::new();
+        runner.concurrency(2).queue_limit(4);
+        let updates = vec![
+            message(1, 1),
+            message(2, 1),
+            message(3, 1),
+            message(4, 1),
+            message(5, 2),
+        ];
+        runner
+            .run(stream::iter(updates), move |update| {
+                let log = handler_log.clone();
+                async move {
+                    if update.id < 5 {
+                        delay_for(Duration::from_millis(20)).await;
+                    }
+                    log.lock().unwrap().push(update.id);
+                    Ok(())
+                }
+            })
+            .await;
+
+        // Queued updates of a busy chat don't hold back other chats.
+        assert_eq!(*log.lock().unwrap(), vec![5, 1, 2, 3, 4]);
+    }
+}
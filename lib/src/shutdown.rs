@@ -0,0 +1,350 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use futures::future;
+use tokio::time::{timeout, Instant};
+
+use telegram_bot_raw::{GetUpdates, Integer};
+
+use crate::api::Api;
+use crate::errors::{Error, ErrorKind};
+use crate::offset::AckHandle;
+
+/// Handle for stopping the `UpdatesStream`, clones share the same state.
+///
+/// # Examples
+///
+/// ```rust
+/// # use telegram_bot::Api;
+/// use std::time::Duration;
+/// use futures::StreamExt;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), telegram_bot::Error> {
+/// # let api: Api = Api::new("token");
+/// # if false {
+/// let mut stream = api.stream();
+/// let shutdown = stream.shutdown_handle();
+///
+/// tokio::spawn(async move {
+///     tokio::time::delay_for(Duration::from_secs(3600)).await;
+///     shutdown.shutdown(Duration::from_secs(10)).await.unwrap();
+/// });
+///
+/// while let Some(update) = stream.next().await {
+///     println!("{:?}", update?);
+/// }
+/// # }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    api: Api,
+    state: Arc<Mutex<ShutdownState>>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct ShutdownState {
+    requested: bool,
+    waker: Option<Waker>,
+    last_processed: Option<Integer>,
+    acks: Option<AckHandle>,
+    in_flight: bool,
+    stopped_wakers: Vec<Waker>,
+}
+
+impl ShutdownHandle {
+    pub(crate) fn new(api: Api, state: Arc<Mutex<ShutdownState>>) -> Self {
+        ShutdownHandle { api, state }
+    }
+
+    /// Stop the stream, confirm processed updates to Telegram, so they are not delivered again,
+    /// and wait for in-flight requests started with [`Api::spawn`](struct.Api.html#method.spawn)
+    /// and its variants at most for `deadline`.
+    ///
+    /// Updates are confirmed once the stream has finished or dropped its pending request
+    /// for updates, so the stream has to be polled or dropped within `deadline`.
+    ///
+    /// If an offset store is used, only acknowledged updates are confirmed,
+    /// otherwise all updates returned by the stream are considered processed.
+    /// If the stream doesn't stop or updates can't be confirmed in time, an error
+    /// is returned and unconfirmed updates are delivered again.
+    pub async fn shutdown(&self, deadline: Duration) -> Result<(), Error> {
+        let end = Instant::now() + deadline;
+        let remaining = || end.saturating_duration_since(Instant::now());
+        {
+            let mut state = self.state.lock().unwrap();
+            state.requested = true;
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        }
+
+        let stopped = future::poll_fn(|cx| self.state.lock().unwrap().poll_stopped(cx));
+        if timeout(remaining(), stopped).await.is_err() {
+            return Err(ErrorKind::StreamShutdownTimeout.into());
+        }
+
+        let last_processed = {
+            let state = self.state.lock().unwrap();
+            match state.acks {
+                Some(ref acks) => acks.committed(),
+                None => state.last_processed,
+            }
+        };
+        tracing::trace!(last_processed = ?last_processed, "shutting down stream");
+
+        if let Some(last_processed) = last_processed {
+            let mut get_updates = GetUpdates::new();
+            get_updates.offset(last_processed + 1).limit(1).timeout(0);
+            if self
+                .api
+                .send_timeout(get_updates, remaining())
+                .await?
+                .is_none()
+            {
+                return Err(ErrorKind::StreamShutdownTimeout.into());
+            }
+        }
+
+        let in_flight = self.api.wait_spawned(remaining()).await;
+        if in_flight > 0 {
+            return Err(ErrorKind::ShutdownTimeout(in_flight).into());
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if the shutdown was requested.
+    pub fn is_requested(&self) -> bool {
+        self.state.lock().unwrap().requested
+    }
+}
+
+impl fmt::Debug for ShutdownHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShutdownHandle")
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+impl ShutdownState {
+    /// Returns `true` if the shutdown was requested,
+    /// otherwise the current task is woken when it is.
+    pub(crate) fn poll_requested(&mut self, cx: &mut Context) -> bool {
+        if !self.requested {
+            self.waker = Some(cx.waker().clone());
+        }
+        self.requested
+    }
+
+    /// Returns `Poll::Ready` once the stream has no request for updates in flight.
+    fn poll_stopped(&mut self, cx: &mut Context) -> Poll<()> {
+        if !self.in_flight {
+            return Poll::Ready(());
+        }
+        self.stopped_wakers.push(cx.waker().clone());
+        Poll::Pending
+    }
+
+    pub(crate) fn request_started(&mut self) {
+        self.in_flight = true;
+    }
+
+    pub(crate) fn request_finished(&mut self) {
+        self.in_flight = false;
+        for waker in self.stopped_wakers.drain(..) {
+            waker.wake();
+        }
+    }
+
+    pub(crate) fn processed(&mut self, update_id: Integer) {
+        self.last_processed = Some(update_id);
+    }
+
+    pub(crate) fn set_acks(&mut self, acks: AckHandle) {
+        self.acks = Some(acks);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use futures::channel::oneshot;
+    use futures::{Future, FutureExt, StreamExt};
+    use serde_json::{json, Value};
+    use telegram_bot_raw::{
+        ApiUrl, Body, ChatId, DeleteMessage, HttpRequest, HttpResponse, MessageId,
+    };
+
+    use super::*;
+    use crate::connector::{Connector, MockConnector};
+
+    fn update(update_id: Integer) -> Value {
+        json!({
+            "update_id": update_id,
+            "message": {
+                "message_id": update_id,
+                "date": 0,
+                "from": {"id": 42, "is_bot": false, "first_name": "John"},
+                "chat": {"id": 42, "type": "private", "first_name": "John"},
+                "text": "Hello!",
+            },
+        })
+    }
+
+    #[tokio::test]
+    async fn test_shutdown() {
+        let mock = MockConnector::new();
+        mock.on("getUpdates", |request| {
+            match request.params["offset"].as_i64() {
+                Some(1) => MockConnector::ok(json!([update(10), update(11)])),
+                _ => MockConnector::ok(json!([])),
+            }
+        });
+        mock.on("deleteMessage", |_| {
+            MockConnector::error(400, "Bad Request")
+        });
+
+        let api = Api::with_connector("token", Box::new(mock.clone()));
+        let mut stream = api.stream();
+        let shutdown = stream.shutdown_handle();
+        assert_eq!(stream.next().await.unwrap().unwrap().id, 10);
+
+        let (sender, receiver) = oneshot::channel();
+        api.spawn_with_error_handler(
+            DeleteMessage::new(ChatId::new(42), MessageId::new(1)),
+            move |error| sender.send(error.to_string()).unwrap(),
+        );
+        let handle = api.spawn_with_handle(DeleteMessage::new(ChatId::new(42), MessageId::new(1)));
+
+        shutdown.shutdown(Duration::from_secs(1)).await.unwrap();
+        assert!(shutdown.is_requested());
+        assert!(stream.next().await.is_none());
+        assert!(mock.sent("getUpdates", json!({"offset": 11, "limit": 1})));
+
+        assert_eq!(receiver.await.unwrap(), "Bad Request");
+        assert!(handle.await.unwrap().is_err());
+        assert_eq!(api.wait_spawned(Duration::from_secs(0)).await, 0);
+    }
+
+    /// Long polls never finish, confirming updates while one is pending is a conflict.
+    #[derive(Debug, Default)]
+    struct LongPollConnector {
+        polling: Arc<AtomicBool>,
+    }
+
+    struct Polling(Arc<AtomicBool>);
+
+    impl Drop for Polling {
+        fn drop(&mut self) {
+            self.0.store(false, Ordering::SeqCst);
+        }
+    }
+
+    impl Connector for LongPollConnector {
+        fn request(
+            &self,
+            _api_url: &ApiUrl,
+            _token: &str,
+            req: HttpRequest,
+        ) -> Pin<Box<dyn Future<Output = Result<HttpResponse, Error>> + Send>> {
+            let params: Value = match req.body {
+                Body::Json(ref body) => serde_json::from_str(body).unwrap(),
+                _ => Value::Null,
+            };
+            if params["offset"] == json!(1) {
+                return future::ready(Ok(MockConnector::ok(json!([update(10)])))).boxed();
+            }
+            if params["timeout"] == json!(0) {
+                let response = if self.polling.load(Ordering::SeqCst) {
+                    MockConnector::error(409, "Conflict")
+                } else {
+                    MockConnector::ok(json!([]))
+                };
+                return future::ready(Ok(response)).boxed();
+            }
+
+            self.polling.store(true, Ordering::SeqCst);
+            let polling = Polling(self.polling.clone());
+            async move {
+                let _polling = polling;
+                future::pending().await
+            }
+            .boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_long_poll() {
+        let api = Api::with_connector("token", Box::new(LongPollConnector::default()));
+        let mut stream = api.stream();
+        let shutdown = stream.shutdown_handle();
+        assert_eq!(stream.next().await.unwrap().unwrap().id, 10);
+
+        // The stream isn't polled, so its long poll is never dropped.
+        assert!(futures::poll!(stream.next()).is_pending());
+        assert!(shutdown.shutdown(Duration::from_millis(50)).await.is_err());
+
+        drop(stream);
+        shutdown.shutdown(Duration::from_secs(1)).await.unwrap();
+
+        let mut stream = api.stream();
+        let shutdown = stream.shutdown_handle();
+        assert_eq!(stream.next().await.unwrap().unwrap().id, 10);
+        assert!(futures::poll!(stream.next()).is_pending());
+        let polled = tokio::spawn(async move { stream.next().await.is_none() });
+        shutdown.shutdown(Duration::from_secs(1)).await.unwrap();
+        assert!(polled.await.unwrap());
+    }
+
+    /// Answers the first request for updates, all other requests never finish.
+    #[derive(Debug)]
+    struct HangingConnector;
+
+    impl Connector for HangingConnector {
+        fn request(
+            &self,
+            _api_url: &ApiUrl,
+            _token: &str,
+            req: HttpRequest,
+        ) -> Pin<Box<dyn Future<Output = Result<HttpResponse, Error>> + Send>> {
+            let params: Value = match req.body {
+                Body::Json(ref body) => serde_json::from_str(body).unwrap(),
+                _ => Value::Null,
+            };
+            if params["offset"] == json!(1) {
+                return future::ready(Ok(MockConnector::ok(json!([update(10)])))).boxed();
+            }
+            future::pending().boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_deadline() {
+        let api = Api::with_connector("token", Box::new(HangingConnector));
+        let mut stream = api.stream();
+        let shutdown = stream.shutdown_handle();
+        assert_eq!(stream.next().await.unwrap().unwrap().id, 10);
+        drop(stream);
+        api.spawn(DeleteMessage::new(ChatId::new(42), MessageId::new(1)));
+
+        // Confirming updates times out, so it is reported and the shutdown
+        // takes no longer than the deadline.
+        let start = Instant::now();
+        let error = shutdown
+            .shutdown(Duration::from_millis(200))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "timed out waiting for the updates stream to stop"
+        );
+        assert!(start.elapsed() < Duration::from_millis(350));
+    }
+}
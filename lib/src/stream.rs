@@ -2,20 +2,25 @@ use std::cmp::max;
 use std::collections::VecDeque;
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::{Arc, Mutex};
 use std::task::Context;
 use std::task::Poll;
 use std::time::Duration;
 
+use futures::future::{abortable, AbortHandle};
 use futures::Stream;
+use tokio::time::delay_for;
 
 use telegram_bot_raw::{AllowedUpdate, GetUpdates, Integer, Update};
 
 use crate::api::Api;
-use crate::errors::Error;
+use crate::errors::{Error, ErrorKind};
+use crate::offset::{AckHandle, OffsetStore};
+use crate::retry::BackoffPolicy;
+use crate::shutdown::{ShutdownHandle, ShutdownState};
 
 const TELEGRAM_LONG_POLL_TIMEOUT_SECONDS: u64 = 5;
 const TELEGRAM_LONG_POLL_LIMIT_MESSAGES: Integer = 100;
-const TELEGRAM_LONG_POLL_ERROR_DELAY_MILLISECONDS: u64 = 500;
 
 /// This type represents stream of Telegram API updates and uses
 /// long polling method under the hood.
@@ -29,7 +34,11 @@ pub struct UpdatesStream {
     timeout: Duration,
     allowed_updates: Vec<AllowedUpdate>,
     limit: Integer,
-    error_delay: Duration,
+    backoff: BackoffPolicy,
+    errors: usize,
+    prefetch: bool,
+    acks: Option<AckHandle>,
+    shutdown: Arc<Mutex<ShutdownState>>,
     next_poll_id: usize,
 }
 
@@ -45,10 +54,26 @@ impl Stream for UpdatesStream {
 
         tracing::trace!("start stream polling");
 
+        let mut shutdown = ref_mut.shutdown.lock().unwrap();
+        if shutdown.poll_requested(cx) {
+            tracing::trace!("stream is shut down");
+            drop(shutdown);
+            ref_mut.current_request = None;
+            ref_mut.buffer.clear();
+            return Poll::Ready(None);
+        }
+
         if let Some(value) = ref_mut.buffer.pop_front() {
             tracing::trace!(update = ?value, "returning buffered update");
+            shutdown.processed(value.id);
+            drop(shutdown);
+            if ref_mut.prefetch && ref_mut.acks.is_none() && ref_mut.current_request.is_none() {
+                tracing::trace!("prefetching next batch");
+                ref_mut.start_request(ref_mut.last_update, true);
+            }
             return Poll::Ready(Some(Ok(value)));
         }
+        drop(shutdown);
         tracing::trace!("processing request");
 
         let result = match ref_mut.current_request {
@@ -66,17 +91,23 @@ impl Stream for UpdatesStream {
                     }
                     Poll::Ready(Ok(None)) => {
                         tracing::trace!("request timed out");
+                        ref_mut.errors = 0;
                         Ok(false)
                     }
                     Poll::Ready(Ok(Some(ref updates))) if updates.is_empty() => {
                         tracing::trace!("request resolved to empty update list");
+                        ref_mut.errors = 0;
                         Ok(false)
                     }
                     Poll::Ready(Ok(Some(updates))) => {
+                        ref_mut.errors = 0;
                         for update in updates {
                             tracing::trace!(update = ?update, "processing update");
                             ref_mut.last_update = max(update.id, ref_mut.last_update);
                             tracing::trace!(last_update = ref_mut.last_update);
+                            if let Some(ref acks) = ref_mut.acks {
+                                acks.receive(update.id);
+                            }
                             ref_mut.buffer.push_back(update)
                         }
 
@@ -84,6 +115,7 @@ impl Stream for UpdatesStream {
                     }
                     Poll::Ready(Err(err)) => {
                         tracing::error!(error = %err, "request error");
+                        ref_mut.errors += 1;
                         Err(err)
                     }
                 }
@@ -92,35 +124,23 @@ impl Stream for UpdatesStream {
 
         match result {
             Err(err) => {
-                let timeout = ref_mut.timeout + Duration::from_secs(1);
-                let mut get_updates = GetUpdates::new();
-                get_updates
-                    .offset(ref_mut.last_update + 1)
-                    .timeout(ref_mut.error_delay.as_secs() as Integer)
-                    .limit(ref_mut.limit)
-                    .allowed_updates(&ref_mut.allowed_updates);
-                tracing::trace!(request = ?get_updates, timeout=?timeout, "preparing new request");
-
-                let request = ref_mut.api.send_timeout(get_updates, timeout);
-                ref_mut.current_request = Some(Box::pin(request));
+                ref_mut.current_request = None;
                 return Poll::Ready(Some(Err(err)));
             }
-            Ok(false) => {
-                let timeout = ref_mut.timeout + Duration::from_secs(1);
-                let mut get_updates = GetUpdates::new();
-                get_updates
-                    .offset(ref_mut.last_update + 1)
-                    .timeout(ref_mut.error_delay.as_secs() as Integer)
-                    .limit(ref_mut.limit)
-                    .allowed_updates(&ref_mut.allowed_updates);
-                tracing::trace!(request = ?get_updates, timeout=?timeout, "preparing new request");
-
-                let request = ref_mut.api.send_timeout(get_updates, timeout);
-                ref_mut.current_request = Some(Box::pin(request));
-
-                tracing::trace!("executing recursive call");
-                Pin::new(ref_mut).poll_next(cx)
-            }
+            Ok(false) => match ref_mut.prepare_request(cx) {
+                Ok(true) => {
+                    tracing::trace!("executing recursive call");
+                    Pin::new(ref_mut).poll_next(cx)
+                }
+                Ok(false) => {
+                    tracing::trace!("waiting for acknowledgements");
+                    Poll::Pending
+                }
+                Err(err) => {
+                    tracing::error!(error = %err, "unable to load offset");
+                    Poll::Ready(Some(Err(err)))
+                }
+            },
             Ok(true) => {
                 tracing::trace!("dropping request");
                 ref_mut.current_request = None;
@@ -132,6 +152,63 @@ impl Stream for UpdatesStream {
 }
 
 impl UpdatesStream {
+    /// Create a new request for updates, returns `false` if the stream
+    /// has to wait for acknowledgements first.
+    fn prepare_request(&mut self, cx: &mut Context) -> Result<bool, Error> {
+        let last_update = match self.acks {
+            Some(ref acks) => match acks.poll_committed(cx)? {
+                Some(committed) => committed,
+                None => return Ok(false),
+            },
+            None => self.last_update,
+        };
+
+        self.start_request(last_update, false);
+        Ok(true)
+    }
+
+    /// Start a request for updates following `last_update`, delayed according
+    /// to the backoff policy after errors. A `background` request is spawned,
+    /// so it makes progress while the stream isn't polled.
+    fn start_request(&mut self, last_update: Integer, background: bool) {
+        let timeout = self.timeout + Duration::from_secs(1);
+        let delay = self.backoff.delay(self.errors);
+        let mut get_updates = GetUpdates::new();
+        get_updates
+            .offset(last_update + 1)
+            .timeout(self.timeout.as_secs() as Integer)
+            .limit(self.limit)
+            .allowed_updates(&self.allowed_updates);
+        tracing::trace!(request = ?get_updates, timeout=?timeout, delay=?delay, "preparing new request");
+
+        let request = self.api.send_timeout(get_updates, timeout);
+        let in_flight = InFlight::new(self.shutdown.clone());
+        let request = async move {
+            let _in_flight = in_flight;
+            if delay > Duration::from_secs(0) {
+                delay_for(delay).await;
+            }
+            request.await
+        };
+
+        if !background {
+            self.current_request = Some(Box::pin(request));
+            return;
+        }
+
+        let (request, abort_handle) = abortable(request);
+        let handle = tokio::spawn(request);
+        let guard = AbortOnDrop(abort_handle);
+        self.current_request = Some(Box::pin(async move {
+            let _guard = guard;
+            match handle.await {
+                Ok(Ok(result)) => result,
+                Ok(Err(_)) => unreachable!("request is aborted only when dropped"),
+                Err(error) => Err(ErrorKind::PrefetchFailed(error).into()),
+            }
+        }));
+    }
+
     ///  create a new `UpdatesStream` instance.
     pub fn new(api: &Api) -> Self {
         UpdatesStream {
@@ -142,7 +219,11 @@ impl UpdatesStream {
             timeout: Duration::from_secs(TELEGRAM_LONG_POLL_TIMEOUT_SECONDS),
             allowed_updates: Vec::new(),
             limit: TELEGRAM_LONG_POLL_LIMIT_MESSAGES,
-            error_delay: Duration::from_millis(TELEGRAM_LONG_POLL_ERROR_DELAY_MILLISECONDS),
+            backoff: BackoffPolicy::new(),
+            errors: 0,
+            prefetch: false,
+            acks: None,
+            shutdown: Default::default(),
             next_poll_id: 0,
         }
     }
@@ -185,9 +266,213 @@ impl UpdatesStream {
     /// Set a delay between erroneous request and next request.
     /// This delay prevents busy looping in some cases.
     ///
+    /// This is a shorthand for the initial delay of the [`backoff`](#method.backoff) policy.
+    ///
     /// Default delay is 500 ms.
     pub fn error_delay(&mut self, delay: Duration) -> &mut Self {
-        self.error_delay = delay;
+        self.backoff.initial_delay(delay);
+        self
+    }
+
+    /// Set a policy of delaying requests after consecutive errors.
+    ///
+    /// By default the delay starts at 500 ms and doubles after every error up to 30 seconds.
+    pub fn backoff(&mut self, backoff: BackoffPolicy) -> &mut Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Request the next batch of updates in the background as soon as the current one
+    /// is received, instead of waiting until all buffered updates are consumed.
+    ///
+    /// Telegram considers updates confirmed when the next batch is requested,
+    /// so buffered updates are not delivered again if the stream is dropped.
+    /// Prefetching is not used with an [`offset_store`](#method.offset_store).
+    ///
+    /// Disabled by default.
+    pub fn prefetch(&mut self, prefetch: bool) -> &mut Self {
+        self.prefetch = prefetch;
+        self
+    }
+    /// Continue from the offset saved in the `store` and commit offsets of processed updates
+    /// to it, so no updates are lost after a restart. Every received update has to be
+    /// acknowledged using the [`AckHandle`](struct.AckHandle.html) returned by
+    /// [`ack_handle`](#method.ack_handle), the next batch of updates is requested only
+    /// when all received updates are acknowledged.
+    ///
+    /// By default updates are confirmed as soon as they are received.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use telegram_bot::{Api, FileOffsetStore};
+    /// use futures::StreamExt;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), telegram_bot::Error> {
+    /// # let api: Api = Api::new("token");
+    /// # if false {
+    /// let mut stream = api.stream();
+    /// stream.offset_store(FileOffsetStore::new("offset.txt"));
+    /// let acks = stream.ack_handle().unwrap();
+    ///
+    /// while let Some(update) = stream.next().await {
+    ///     let update = update?;
+    ///     println!("{:?}", update);
+    ///     acks.ack(&update)?;
+    /// }
+    /// # }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn offset_store<S: OffsetStore + 'static>(&mut self, store: S) -> &mut Self {
+        let acks = AckHandle::new(Box::new(store));
+        self.shutdown.lock().unwrap().set_acks(acks.clone());
+        self.acks = Some(acks);
         self
     }
+
+    /// Handle for acknowledging processed updates, if an offset store is used.
+    pub fn ack_handle(&self) -> Option<AckHandle> {
+        self.acks.clone()
+    }
+
+    /// Handle for stopping the stream gracefully.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle::new(self.api.clone(), self.shutdown.clone())
+    }
+}
+
+/// Marks the request for updates in flight until it is finished or dropped.
+struct InFlight(Arc<Mutex<ShutdownState>>);
+
+impl InFlight {
+    fn new(shutdown: Arc<Mutex<ShutdownState>>) -> Self {
+        shutdown.lock().unwrap().request_started();
+        InFlight(shutdown)
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.lock().unwrap().request_finished();
+    }
+}
+
+struct AbortOnDrop(AbortHandle);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Instant;
+
+    use futures::StreamExt;
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::connector::MockConnector;
+
+    fn update(update_id: Integer) -> Value {
+        json!({
+            "update_id": update_id,
+            "message": {
+                "message_id": update_id,
+                "date": 0,
+                "from": {"id": 42, "is_bot": false, "first_name": "John"},
+                "chat": {"id": 42, "type": "private", "first_name": "John"},
+                "text": "Hello!",
+            },
+        })
+    }
+
+    #[tokio::test]
+    async fn test_long_polling() {
+        let mock = MockConnector::new();
+        mock.push(MockConnector::ok(json!([update(1)])));
+        mock.push(MockConnector::ok(json!([update(2)])));
+
+        let api = Api::with_connector("token", Box::new(mock.clone()));
+        let mut stream = api.stream();
+        assert_eq!(stream.next().await.unwrap().unwrap().id, 1);
+        assert!(mock.sent("getUpdates", json!({"offset": 1, "timeout": 5})));
+
+        stream.timeout(Duration::from_secs(30));
+        assert_eq!(stream.next().await.unwrap().unwrap().id, 2);
+        assert!(mock.sent("getUpdates", json!({"offset": 2, "timeout": 30})));
+    }
+
+    #[tokio::test]
+    async fn test_backoff() {
+        let mock = MockConnector::new();
+        mock.push(MockConnector::error(502, "Bad Gateway"));
+        mock.push(MockConnector::error(502, "Bad Gateway"));
+        mock.push(MockConnector::ok(json!([update(1)])));
+
+        let mut backoff = BackoffPolicy::new();
+        backoff
+            .initial_delay(Duration::from_millis(50))
+            .max_delay(Duration::from_millis(500));
+
+        let api = Api::with_connector("token", Box::new(mock.clone()));
+        let mut stream = api.stream();
+        stream.backoff(backoff);
+
+        let start = Instant::now();
+        assert!(stream.next().await.unwrap().is_err());
+        assert!(stream.next().await.unwrap().is_err());
+        assert_eq!(stream.next().await.unwrap().unwrap().id, 1);
+        assert!(start.elapsed() >= Duration::from_millis(150));
+        assert_eq!(stream.errors, 0);
+    }
+
+    #[tokio::test]
+    async fn test_prefetch() {
+        let mock = MockConnector::new();
+        mock.on("getUpdates", |request| {
+            match request.params["offset"].as_i64() {
+                Some(1) => MockConnector::ok(json!([update(1), update(2)])),
+                Some(3) => MockConnector::ok(json!([update(3)])),
+                _ => MockConnector::ok(json!([])),
+            }
+        });
+
+        let api = Api::with_connector("token", Box::new(mock.clone()));
+        let mut stream = api.stream();
+        stream.prefetch(true);
+
+        assert_eq!(stream.next().await.unwrap().unwrap().id, 1);
+        delay_for(Duration::from_millis(50)).await;
+        assert_eq!(mock.requests_to("getUpdates").len(), 2);
+        assert_eq!(stream.next().await.unwrap().unwrap().id, 2);
+        assert_eq!(stream.next().await.unwrap().unwrap().id, 3);
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_failed() {
+        let panicked = AtomicBool::new(false);
+        let mock = MockConnector::new();
+        mock.on("getUpdates", move |request| {
+            match request.params["offset"].as_i64() {
+                Some(1) => MockConnector::ok(json!([update(1)])),
+                Some(2) if !panicked.swap(true, Ordering::SeqCst) => panic!("connector panicked"),
+                Some(2) => MockConnector::ok(json!([update(2)])),
+                _ => MockConnector::ok(json!([])),
+            }
+        });
+
+        let api = Api::with_connector("token", Box::new(mock.clone()));
+        let mut stream = api.stream();
+        stream.prefetch(true);
+
+        assert_eq!(stream.next().await.unwrap().unwrap().id, 1);
+        let error = stream.next().await.unwrap().unwrap_err();
+        assert!(error.to_string().starts_with("prefetch request failed"));
+        assert_eq!(stream.next().await.unwrap().unwrap().id, 2);
+    }
 }
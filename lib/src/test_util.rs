@@ -0,0 +1,9 @@
+use std::path::PathBuf;
+
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+
+/// Unique path in the temporary directory, so concurrent test runs don't collide.
+pub(crate) fn temp_path(name: &str) -> PathBuf {
+    let suffix: String = thread_rng().sample_iter(&Alphanumeric).take(12).collect();
+    std::env::temp_dir().join(format!("telegram-bot-test-{}-{}", suffix, name))
+}
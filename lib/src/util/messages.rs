@@ -4,7 +4,7 @@
 
 use crate::prelude::CanGetFile;
 use crate::types::{
-    requests::get_file::GetFile, ChannelPost, Message, MessageKind, MessageOrChannelPost,
+    requests::get_file::GetFile, ChannelPost, Message, MessageKind, MessageOrChannelPost, RichText,
 };
 
 /// A trait to obtain text from a message.
@@ -69,6 +69,48 @@ impl MessageText for ChannelPost {
     }
 }
 
+/// A trait to obtain formatted text from a message.
+///
+/// Entities of text messages are preserved, so the text can be rendered back
+/// to HTML or MarkdownV2, other messages return the plain text of [`MessageText`].
+///
+/// [`MessageText`]: trait.MessageText.html
+pub trait MessageRichText {
+    /// Obtain formatted text from a message if available.
+    fn rich_text(&self) -> Option<RichText>;
+}
+
+impl MessageRichText for MessageOrChannelPost {
+    fn rich_text(&self) -> Option<RichText> {
+        match self {
+            MessageOrChannelPost::Message(msg) => msg.rich_text(),
+            MessageOrChannelPost::ChannelPost(post) => post.rich_text(),
+        }
+    }
+}
+
+impl MessageRichText for Message {
+    fn rich_text(&self) -> Option<RichText> {
+        self.kind.rich_text()
+    }
+}
+
+impl MessageRichText for MessageKind {
+    fn rich_text(&self) -> Option<RichText> {
+        match self {
+            MessageKind::Text { data, entities } => Some(RichText::from_entities(data, entities)),
+            MessageKind::PinnedMessage { data } => data.rich_text(),
+            kind => kind.text().map(RichText::from),
+        }
+    }
+}
+
+impl MessageRichText for ChannelPost {
+    fn rich_text(&self) -> Option<RichText> {
+        self.kind.rich_text()
+    }
+}
+
 /// A trait to obtain `GetFile` requests from a message.
 ///
 /// Many message kinds such as `Sticker` return a single `GetFile`.
@@ -0,0 +1,387 @@
+use std::cmp::max;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+
+use futures::future::BoxFuture;
+use futures::{future, FutureExt, Stream};
+use hyper::body::HttpBody;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+
+use telegram_bot_raw::Update;
+
+use crate::errors::{Error, ErrorKind};
+
+const TELEGRAM_WEBHOOK_DEFAULT_PATH: &str = "/";
+const TELEGRAM_WEBHOOK_DEFAULT_CAPACITY: usize = 100;
+const TELEGRAM_WEBHOOK_SECRET_TOKEN_HEADER: &str = "x-telegram-bot-api-secret-token";
+const TELEGRAM_WEBHOOK_MAX_BODY_SIZE: usize = 4 * 1024 * 1024;
+
+/// This type represents stream of Telegram API updates and uses
+/// an embedded HTTP server receiving webhook requests under the hood.
+///
+/// The server is started when the stream is polled for the first time
+/// and stopped when the stream is dropped.
+///
+/// Received updates are queued until the stream is polled. Telegram considers an update
+/// delivered once it is queued, so queued updates are lost if the stream is dropped.
+/// When the queue is full, requests are answered with `503 Service Unavailable`
+/// and Telegram delivers the updates again later. Request bodies larger than 4 MiB
+/// are answered with `413 Payload Too Large`.
+///
+/// # Examples
+///
+/// ```rust
+/// # use telegram_bot::WebhookStream;
+/// use futures::StreamExt;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// # if false {
+/// let mut stream = WebhookStream::new(([0, 0, 0, 0], 8443));
+/// stream.path("/telegram");
+///
+/// let update = stream.next().await;
+///     println!("{:?}", update);
+/// # }
+/// # }
+/// ```
+#[must_use = "streams do nothing unless polled"]
+pub struct WebhookStream {
+    addr: SocketAddr,
+    path: String,
+    secret_token: Option<String>,
+    capacity: usize,
+    local_addr: Option<SocketAddr>,
+    server: Option<BoxFuture<'static, Result<(), Error>>>,
+    receiver: Option<Receiver<Update>>,
+}
+
+impl Stream for WebhookStream {
+    type Item = Result<Update, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let ref_mut = self.get_mut();
+
+        if ref_mut.receiver.is_none() {
+            tracing::trace!(addr = %ref_mut.addr, path = %ref_mut.path, "starting webhook server");
+            let (sender, receiver) = channel(max(ref_mut.capacity, 1));
+            let endpoint = Endpoint {
+                path: ref_mut.path.clone(),
+                secret_token: ref_mut.secret_token.clone(),
+            };
+            ref_mut.server = Some(match serve(ref_mut.addr, endpoint, sender) {
+                Ok((local_addr, server)) => {
+                    ref_mut.local_addr = Some(local_addr);
+                    server
+                }
+                Err(err) => future::err(err).boxed(),
+            });
+            ref_mut.receiver = Some(receiver);
+        }
+
+        if let Some(ref mut server) = ref_mut.server {
+            if let Poll::Ready(result) = server.poll_unpin(cx) {
+                tracing::trace!("webhook server stopped");
+                ref_mut.server = None;
+                if let Err(err) = result {
+                    tracing::error!(error = %err, "webhook server error");
+                    return Poll::Ready(Some(Err(err)));
+                }
+            }
+        }
+
+        match ref_mut.receiver {
+            Some(ref mut receiver) => receiver.poll_recv(cx).map(|update| update.map(Ok)),
+            None => Poll::Ready(None),
+        }
+    }
+}
+
+impl WebhookStream {
+    /// Create a new `WebhookStream` instance listening on `addr`.
+    pub fn new<A: Into<SocketAddr>>(addr: A) -> Self {
+        WebhookStream {
+            addr: addr.into(),
+            path: TELEGRAM_WEBHOOK_DEFAULT_PATH.to_string(),
+            secret_token: None,
+            capacity: TELEGRAM_WEBHOOK_DEFAULT_CAPACITY,
+            local_addr: None,
+            server: None,
+            receiver: None,
+        }
+    }
+
+    /// Set the path on which updates are accepted, this should correspond with
+    /// the path of `url` passed to [setWebhook](https://core.telegram.org/bots/api#setwebhook).
+    /// Requests to other paths are answered with `404 Not Found`.
+    ///
+    /// Default path is `/`.
+    pub fn path<P: AsRef<str>>(&mut self, path: P) -> &mut Self {
+        self.path = path.as_ref().to_string();
+        self
+    }
+
+    /// Set the secret token which is expected in the `X-Telegram-Bot-Api-Secret-Token` header,
+    /// this corresponds with `secret_token` field in
+    /// [setWebhook](https://core.telegram.org/bots/api#setwebhook) method.
+    /// Requests without matching header are answered with `401 Unauthorized`.
+    ///
+    /// By default the header is not checked.
+    pub fn secret_token<T: AsRef<str>>(&mut self, secret_token: T) -> &mut Self {
+        self.secret_token = Some(secret_token.as_ref().to_string());
+        self
+    }
+
+    /// Set the number of received updates which are queued until the stream is polled,
+    /// at least one update is always queued.
+    ///
+    /// Default capacity is 100 updates.
+    pub fn capacity(&mut self, capacity: usize) -> &mut Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Address the server listens on, available once the stream is polled for the first time.
+    /// It differs from the address passed to [`new`](#method.new) when it has port 0.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+}
+
+struct Endpoint {
+    path: String,
+    secret_token: Option<String>,
+}
+
+fn serve(
+    addr: SocketAddr,
+    endpoint: Endpoint,
+    sender: Sender<Update>,
+) -> Result<(SocketAddr, BoxFuture<'static, Result<(), Error>>), Error> {
+    let endpoint = Arc::new(endpoint);
+    let make_service = make_service_fn(move |_| {
+        let endpoint = endpoint.clone();
+        let sender = sender.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                handle(request, endpoint.clone(), sender.clone())
+            }))
+        }
+    });
+
+    let server = Server::try_bind(&addr)
+        .map_err(ErrorKind::from)?
+        .serve(make_service);
+    let local_addr = server.local_addr();
+    let server = server.map(|result| result.map_err(|err| ErrorKind::from(err).into()));
+    Ok((local_addr, server.boxed()))
+}
+
+async fn handle(
+    request: Request<Body>,
+    endpoint: Arc<Endpoint>,
+    mut sender: Sender<Update>,
+) -> Result<Response<Body>, Infallible> {
+    let authorized = match endpoint.secret_token {
+        Some(ref secret_token) => {
+            match request.headers().get(TELEGRAM_WEBHOOK_SECRET_TOKEN_HEADER) {
+                Some(value) => constant_time_eq(value.as_bytes(), secret_token.as_bytes()),
+                None => false,
+            }
+        }
+        None => true,
+    };
+
+    let status = if request.uri().path() != endpoint.path {
+        StatusCode::NOT_FOUND
+    } else if request.method() != Method::POST {
+        StatusCode::METHOD_NOT_ALLOWED
+    } else if !authorized {
+        tracing::error!("webhook request with invalid secret token");
+        StatusCode::UNAUTHORIZED
+    } else {
+        match read_body(request.into_body(), TELEGRAM_WEBHOOK_MAX_BODY_SIZE).await {
+            Err(err) => {
+                tracing::error!(error = %err, "unable to read webhook request");
+                StatusCode::BAD_REQUEST
+            }
+            Ok(None) => {
+                tracing::error!("webhook request is too large");
+                StatusCode::PAYLOAD_TOO_LARGE
+            }
+            Ok(Some(body)) => match serde_json::from_slice::<Update>(&body) {
+                Err(err) => {
+                    tracing::error!(error = %err, "unable to deserialize webhook update");
+                    StatusCode::BAD_REQUEST
+                }
+                Ok(update) => {
+                    tracing::trace!(update = ?update, "received update");
+                    match sender.try_send(update) {
+                        Ok(()) => StatusCode::OK,
+                        Err(_) => {
+                            tracing::error!("webhook update queue is full or closed");
+                            StatusCode::SERVICE_UNAVAILABLE
+                        }
+                    }
+                }
+            },
+        }
+    };
+
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    Ok(response)
+}
+
+/// Read the whole `body`, returns `None` if it is longer than `limit` bytes.
+async fn read_body(mut body: Body, limit: usize) -> Result<Option<Vec<u8>>, hyper::Error> {
+    if HttpBody::size_hint(&body).lower() > limit as u64 {
+        return Ok(None);
+    }
+
+    let mut data = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if data.len() + chunk.len() > limit {
+            return Ok(None);
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(Some(data))
+}
+
+/// Compare secrets in time which doesn't depend on the position of the first difference.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::channel::mpsc::unbounded;
+    use futures::StreamExt;
+    use hyper::Client;
+
+    use super::*;
+
+    const UPDATE: &str = r#"{
+        "update_id": 1,
+        "message": {
+            "message_id": 1,
+            "date": 0,
+            "from": {"id": 1, "is_bot": false, "first_name": "John"},
+            "chat": {"id": 1, "type": "private", "first_name": "John"},
+            "text": "Hello!"
+        }
+    }"#;
+
+    #[tokio::test]
+    async fn test_webhook() {
+        let mut stream = WebhookStream::new(([127, 0, 0, 1], 0));
+        stream.path("/telegram").secret_token("secret");
+        assert!(futures::poll!(stream.next()).is_pending());
+        let addr = stream.local_addr().unwrap();
+        assert_ne!(addr.port(), 0);
+
+        let (sender, mut updates) = unbounded();
+        tokio::spawn(async move {
+            while let Some(update) = stream.next().await {
+                let _ = sender.unbounded_send(update);
+            }
+        });
+
+        let client = Client::new();
+        let send = |method: Method, path: &str, secret: &str, body: &'static str| {
+            let request = Request::builder()
+                .method(method)
+                .uri(format!("http://{}{}", addr, path))
+                .header(TELEGRAM_WEBHOOK_SECRET_TOKEN_HEADER, secret)
+                .body(Body::from(body))
+                .unwrap();
+            let response = client.request(request);
+            async move { response.await.unwrap().status() }
+        };
+
+        let status = send(Method::POST, "/other", "secret", UPDATE).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let status = send(Method::GET, "/telegram", "secret", "").await;
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+        let status = send(Method::POST, "/telegram", "wrong", UPDATE).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let status = send(Method::POST, "/telegram", "secret", "{").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let status = send(Method::POST, "/telegram", "secret", UPDATE).await;
+        assert_eq!(status, StatusCode::OK);
+        let update = updates.next().await.unwrap().unwrap();
+        assert_eq!(update.id, 1);
+        assert!(updates.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_webhook_full() {
+        let endpoint = Arc::new(Endpoint {
+            path: TELEGRAM_WEBHOOK_DEFAULT_PATH.to_string(),
+            secret_token: None,
+        });
+        let (sender, mut receiver) = channel(1);
+        let send = || {
+            let request = Request::builder()
+                .method(Method::POST)
+                .uri(TELEGRAM_WEBHOOK_DEFAULT_PATH)
+                .body(Body::from(UPDATE))
+                .unwrap();
+            let response = handle(request, endpoint.clone(), sender.clone());
+            async move { response.await.unwrap().status() }
+        };
+
+        assert_eq!(send().await, StatusCode::OK);
+        assert_eq!(send().await, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(receiver.recv().await.unwrap().id, 1);
+        assert_eq!(send().await, StatusCode::OK);
+
+        drop(receiver);
+        assert_eq!(send().await, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn test_body_limit() {
+        let read = |body| async move { read_body(body, 4).await.unwrap() };
+        assert_eq!(read(Body::from("abcd")).await, Some(b"abcd".to_vec()));
+        assert_eq!(read(Body::from("abcde")).await, None);
+
+        // Chunked bodies without length are cut while reading.
+        let chunks = futures::stream::iter(vec![Ok::<_, Infallible>("abc"), Ok("de")]);
+        assert_eq!(read(Body::wrap_stream(chunks)).await, None);
+        let chunks = futures::stream::iter(vec![Ok::<_, Infallible>("ab"), Ok("cd")]);
+        assert_eq!(
+            read(Body::wrap_stream(chunks)).await,
+            Some(b"abcd".to_vec())
+        );
+
+        let endpoint = Arc::new(Endpoint {
+            path: TELEGRAM_WEBHOOK_DEFAULT_PATH.to_string(),
+            secret_token: None,
+        });
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(TELEGRAM_WEBHOOK_DEFAULT_PATH)
+            .body(Body::from(vec![b' '; TELEGRAM_WEBHOOK_MAX_BODY_SIZE + 1]))
+            .unwrap();
+        let response = handle(request, endpoint, channel(1).0).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret2"));
+        assert!(constant_time_eq(b"", b""));
+    }
+}
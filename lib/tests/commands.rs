@@ -0,0 +1,90 @@
+use serde_json::json;
+use telegram_bot::{BotCommands, CommandError, Message};
+
+#[derive(Debug, PartialEq, BotCommands)]
+enum Commands {
+    /// Start the bot.
+    Start,
+    /// Set a timer,
+    /// in seconds.
+    SetTimer(u32),
+    #[command(rename = "say", rest)]
+    Echo {
+        text: String,
+    },
+    Ban {
+        user_id: i64,
+        reason: String,
+    },
+}
+
+fn message(text: &str) -> Message {
+    let length = text
+        .split_whitespace()
+        .next()
+        .unwrap()
+        .encode_utf16()
+        .count();
+    serde_json::from_value(json!({
+        "message_id": 1,
+        "date": 0,
+        "from": {"id": 42, "is_bot": false, "first_name": "John"},
+        "chat": {"id": 42, "type": "private", "first_name": "John"},
+        "text": text,
+        "entities": [{"type": "bot_command", "offset": 0, "length": length}],
+    }))
+    .unwrap()
+}
+
+fn parse(text: &str) -> Option<Result<Commands, CommandError>> {
+    Commands::parse(&message(text), "test_bot")
+}
+
+#[test]
+fn test_parse() {
+    assert_eq!(parse("/start"), Some(Ok(Commands::Start)));
+    assert_eq!(parse("/start@test_bot"), Some(Ok(Commands::Start)));
+    assert_eq!(parse("/start@other_bot"), None);
+    assert_eq!(parse("/set_timer 60"), Some(Ok(Commands::SetTimer(60))));
+    assert_eq!(
+        parse("/say  Hello, \"world\"! "),
+        Some(Ok(Commands::Echo {
+            text: "Hello, \"world\"!".to_string()
+        }))
+    );
+    assert_eq!(
+        parse("/ban 42 \"spam links\""),
+        Some(Ok(Commands::Ban {
+            user_id: 42,
+            reason: "spam links".to_string()
+        }))
+    );
+}
+
+#[test]
+fn test_errors() {
+    assert_eq!(
+        parse("/stop"),
+        Some(Err(CommandError::UnknownCommand("stop".to_string())))
+    );
+    assert_eq!(
+        parse("/start now"),
+        Some(Err(CommandError::WrongArgumentsCount {
+            expected: 0,
+            found: 1
+        }))
+    );
+    let error = parse("/set_timer soon").unwrap().unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "invalid argument 1 \"soon\": invalid digit found in string"
+    );
+}
+
+#[test]
+fn test_help() {
+    assert_eq!(
+        Commands::help(),
+        "/start - Start the bot.\n/set_timer - Set a timer, in seconds.\n/say\n/ban"
+    );
+}
@@ -10,13 +10,109 @@ pub struct Error(ErrorKind);
 pub(crate) enum ErrorKind {
     EmptyBody,
     TelegramError {
+        error_code: Option<Integer>,
         description: String,
         parameters: Option<ResponseParameters>,
     },
+    UnexpectedResponse {
+        status: u16,
+        snippet: String,
+    },
     DetachedError(String),
     Json(::serde_json::Error),
 }
 
+/// Classified error returned by Telegram.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub enum ApiError {
+    /// The bot was blocked by the user.
+    BotBlockedByUser,
+    /// The chat doesn't exist or the bot is not a member of the chat.
+    ChatNotFound,
+    /// New content and reply markup of the message are exactly the same as current ones.
+    MessageNotModified,
+    /// The message to edit was deleted or doesn't exist.
+    MessageToEditNotFound,
+    /// Flood control was exceeded, see `ResponseParameters::retry_after`.
+    TooManyRequests,
+    /// Another `getUpdates` request or webhook is active.
+    Conflict,
+    /// The bot token is invalid.
+    Unauthorized,
+    /// Any other error.
+    Other,
+}
+
+impl ApiError {
+    /// Classify the error by `error_code` and `description` returned by Telegram.
+    pub fn classify(error_code: Option<Integer>, description: &str) -> Self {
+        let description = description.to_lowercase();
+        match error_code {
+            Some(401) => ApiError::Unauthorized,
+            Some(409) => ApiError::Conflict,
+            Some(429) => ApiError::TooManyRequests,
+            Some(403) if description.contains("bot was blocked by the user") => {
+                ApiError::BotBlockedByUser
+            }
+            Some(400) if description.contains("chat not found") => ApiError::ChatNotFound,
+            Some(400) if description.contains("message is not modified") => {
+                ApiError::MessageNotModified
+            }
+            Some(400) if description.contains("message to edit not found") => {
+                ApiError::MessageToEditNotFound
+            }
+            _ => ApiError::Other,
+        }
+    }
+}
+
+impl Error {
+    /// Classified error, if the request was rejected by Telegram.
+    pub fn api_error(&self) -> Option<ApiError> {
+        match &self.0 {
+            ErrorKind::TelegramError {
+                error_code,
+                description,
+                ..
+            } => Some(ApiError::classify(*error_code, description)),
+            _ => None,
+        }
+    }
+
+    /// Error code, if the request was rejected by Telegram.
+    pub fn error_code(&self) -> Option<Integer> {
+        match &self.0 {
+            ErrorKind::TelegramError { error_code, .. } => *error_code,
+            _ => None,
+        }
+    }
+
+    /// Human-readable description of the error, if the request was rejected by Telegram.
+    pub fn description(&self) -> Option<&str> {
+        match &self.0 {
+            ErrorKind::TelegramError { description, .. } => Some(description),
+            _ => None,
+        }
+    }
+
+    /// HTTP status code of the response which is not a valid Telegram response,
+    /// e.g. an HTML error page returned by a proxy.
+    pub fn unexpected_status(&self) -> Option<u16> {
+        match &self.0 {
+            ErrorKind::UnexpectedResponse { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+
+    /// Information about why the request was unsuccessful, if provided by Telegram.
+    pub fn parameters(&self) -> Option<&ResponseParameters> {
+        match &self.0 {
+            ErrorKind::TelegramError { parameters, .. } => parameters.as_ref(),
+            _ => None,
+        }
+    }
+}
+
 impl From<::serde_json::Error> for ErrorKind {
     fn from(error: ::serde_json::Error) -> Self {
         ErrorKind::Json(error)
@@ -36,6 +132,7 @@ impl fmt::Display for Error {
             ErrorKind::TelegramError {
                 description,
                 parameters,
+                ..
             } => {
                 f.write_str(&description)?;
                 if let Some(parameters) = parameters {
@@ -48,6 +145,13 @@ impl fmt::Display for Error {
                 }
                 Ok(())
             }
+            ErrorKind::UnexpectedResponse { status, snippet } => {
+                write!(f, "unexpected response with status {}", status)?;
+                if !snippet.is_empty() {
+                    write!(f, ": {}", snippet)?;
+                }
+                Ok(())
+            }
             ErrorKind::DetachedError(s) => f.write_str(&s),
             ErrorKind::Json(error) => write!(f, "{}", error),
         }
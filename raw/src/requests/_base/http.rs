@@ -1,9 +1,10 @@
 use std::fmt;
 
 use bytes::Bytes;
+use serde_json::Value;
 
-use crate::types::Text;
-use crate::url::telegram_api_url;
+use crate::types::{ChatId, ChatRef, Integer, Text};
+use crate::url::ApiUrl;
 
 #[derive(Debug, Clone, PartialEq, PartialOrd, Eq, Ord, Hash)]
 pub enum RequestUrl {
@@ -15,9 +16,9 @@ impl RequestUrl {
         RequestUrl::Method(method)
     }
 
-    pub fn url(&self, token: &str) -> String {
+    pub fn url(&self, api_url: &ApiUrl, token: &str) -> String {
         match self {
-            &RequestUrl::Method(method) => format!("{}bot{}/{}", telegram_api_url(), token, method),
+            &RequestUrl::Method(method) => api_url.method_url(token, method),
         }
     }
 }
@@ -70,9 +71,82 @@ impl HttpRequest {
             RequestUrl::Method(method) => method,
         }
     }
+
+    /// Obtains `chat_id` parameter of the request, if any.
+    pub fn chat_id(&self) -> Option<ChatRef> {
+        let value = match self.body {
+            Body::Json(ref body) => {
+                match serde_json::from_str::<Value>(body).ok()?.get("chat_id")? {
+                    Value::Number(number) => number.to_string(),
+                    Value::String(string) => string.clone(),
+                    _ => return None,
+                }
+            }
+            Body::Multipart(ref parts) => match parts.iter().find(|(key, _)| *key == "chat_id")? {
+                (_, MultipartValue::Text(text)) => text.as_str().to_string(),
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        Some(match value.parse::<Integer>() {
+            Ok(id) => ChatRef::from_chat_id(ChatId::new(id)),
+            Err(_) => ChatRef::ChannelUsername(value),
+        })
+    }
+
+    /// Replace `chat_id` parameter of the request, returns `false` if there is no such parameter.
+    pub fn set_chat_id(&mut self, chat_id: ChatRef) -> bool {
+        match self.body {
+            Body::Json(ref mut body) => {
+                let updated = serde_json::from_str::<Value>(body)
+                    .ok()
+                    .and_then(|mut value| {
+                        *value.get_mut("chat_id")? = serde_json::to_value(&chat_id).ok()?;
+                        serde_json::to_string(&value).ok()
+                    });
+                match updated {
+                    Some(updated) => {
+                        *body = updated;
+                        true
+                    }
+                    None => false,
+                }
+            }
+            Body::Multipart(ref mut parts) => {
+                match parts.iter_mut().find(|(key, _)| *key == "chat_id") {
+                    Some((_, value)) => {
+                        *value = MultipartValue::Text(chat_id.to_string().into());
+                        true
+                    }
+                    None => false,
+                }
+            }
+            _ => false,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, PartialOrd, Eq, Ord, Hash)]
 pub struct HttpResponse {
+    /// HTTP status code.
+    pub status: u16,
+    /// HTTP headers, names are lowercase.
+    pub headers: Vec<(String, String)>,
     pub body: Option<Vec<u8>>,
 }
+
+impl HttpResponse {
+    /// Obtains value of the header with `name`, names are compared case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns `true` if the status code is in the 200-299 range.
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
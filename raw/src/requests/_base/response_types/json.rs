@@ -4,6 +4,8 @@ use serde_json;
 use crate::requests::*;
 use crate::types::*;
 
+const UNEXPECTED_RESPONSE_SNIPPET_LENGTH: usize = 256;
+
 pub trait JsonResponse {
     type Raw;
     type Type;
@@ -42,21 +44,42 @@ where
     type Type = <Resp as JsonResponse>::Type;
 
     fn deserialize(resp: HttpResponse) -> Result<Self::Type, Error> {
-        if let Some(body) = resp.body.as_ref() {
-            let raw = serde_json::from_slice(body).map_err(ErrorKind::from)?;
-            match raw {
-                ResponseWrapper::Success { result } => Ok(<Self as JsonResponse>::map(result)),
-                ResponseWrapper::Error {
-                    description,
-                    parameters,
-                } => Err(ErrorKind::TelegramError {
-                    description,
-                    parameters,
-                }
-                .into()),
+        let raw = match resp.body {
+            Some(ref body) => serde_json::from_slice(body),
+            None if resp.is_success() => return Err(ErrorKind::EmptyBody.into()),
+            None => return Err(unexpected_response(&resp).into()),
+        };
+
+        match raw {
+            Ok(ResponseWrapper::Success { result }) => Ok(<Self as JsonResponse>::map(result)),
+            Ok(ResponseWrapper::Error {
+                error_code,
+                description,
+                parameters,
+            }) => Err(ErrorKind::TelegramError {
+                error_code,
+                description,
+                parameters,
             }
-        } else {
-            Err(ErrorKind::EmptyBody.into())
+            .into()),
+            Err(_) if !resp.is_success() => Err(unexpected_response(&resp).into()),
+            Err(error) => Err(ErrorKind::from(error).into()),
         }
     }
 }
+
+fn unexpected_response(resp: &HttpResponse) -> ErrorKind {
+    let snippet = match resp.body {
+        Some(ref body) => String::from_utf8_lossy(body)
+            .chars()
+            .take(UNEXPECTED_RESPONSE_SNIPPET_LENGTH)
+            .collect::<String>()
+            .trim()
+            .to_string(),
+        None => String::new(),
+    };
+    ErrorKind::UnexpectedResponse {
+        status: resp.status,
+        snippet,
+    }
+}
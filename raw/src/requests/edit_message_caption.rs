@@ -11,6 +11,8 @@ pub struct EditMessageCaption<'s> {
     message_id: MessageId,
     caption: Cow<'s, str>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    caption_entities: Option<Vec<MessageEntity>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     reply_markup: Option<ReplyMarkup>,
 }
 
@@ -34,10 +36,19 @@ impl<'s> EditMessageCaption<'s> {
             chat_id: chat.to_chat_ref(),
             message_id: message_id.to_message_id(),
             caption: caption.into(),
+            caption_entities: None,
             reply_markup: None,
         }
     }
 
+    /// Set the caption with explicit entities.
+    pub fn rich_caption(&mut self, caption: &RichText) -> &mut Self {
+        let (caption, entities) = caption.to_entities();
+        self.caption = caption.into();
+        self.caption_entities = Some(entities);
+        self
+    }
+
     pub fn reply_markup<R>(&mut self, reply_markup: R) -> &mut Self
     where
         R: Into<ReplyMarkup>,
@@ -54,7 +54,7 @@ impl GetUpdates {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize)]
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub enum AllowedUpdate {
     #[serde(rename = "message")]
     Message,
@@ -66,7 +66,7 @@ pub enum AllowedUpdate {
     EditedChannelPost,
     #[serde(rename = "inline_query")]
     InlineQuery,
-    #[serde(rename = "chosen_inline_query")]
+    #[serde(rename = "chosen_inline_result")]
     ChosenInlineResult,
     #[serde(rename = "callback_query")]
     CallbackQuery,
@@ -74,4 +74,11 @@ pub enum AllowedUpdate {
     ShippingQuery,
     #[serde(rename = "pre_checkout_query")]
     PreCheckoutQuery,
+    #[serde(rename = "poll")]
+    Poll,
+    #[serde(rename = "poll_answer")]
+    PollAnswer,
+    #[doc(hidden)]
+    #[serde(other, skip_serializing)]
+    Unknown,
 }
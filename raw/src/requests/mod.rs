@@ -3,6 +3,7 @@ pub mod _base;
 pub mod answer_callback_query;
 pub mod answer_inline_query;
 pub mod delete_message;
+pub mod delete_webhook;
 pub mod edit_message_caption;
 pub mod edit_message_live_location;
 pub mod edit_message_reply_markup;
@@ -17,6 +18,7 @@ pub mod get_file;
 pub mod get_me;
 pub mod get_updates;
 pub mod get_user_profile_photos;
+pub mod get_webhook_info;
 pub mod kick_chat_member;
 pub mod leave_chat;
 pub mod pin_chat_message;
@@ -30,6 +32,7 @@ pub mod send_photo;
 pub mod send_poll;
 pub mod send_venue;
 pub mod send_video;
+pub mod set_webhook;
 pub mod stop_message_live_location;
 pub mod stop_poll;
 pub mod unban_chat_member;
@@ -39,6 +42,7 @@ pub use self::_base::*;
 pub use self::answer_callback_query::*;
 pub use self::answer_inline_query::*;
 pub use self::delete_message::*;
+pub use self::delete_webhook::*;
 pub use self::edit_message_caption::*;
 pub use self::edit_message_live_location::*;
 pub use self::edit_message_reply_markup::*;
@@ -53,6 +57,7 @@ pub use self::get_file::*;
 pub use self::get_me::*;
 pub use self::get_updates::*;
 pub use self::get_user_profile_photos::*;
+pub use self::get_webhook_info::*;
 pub use self::kick_chat_member::*;
 pub use self::leave_chat::*;
 pub use self::pin_chat_message::*;
@@ -66,6 +71,7 @@ pub use self::send_photo::*;
 pub use self::send_poll::*;
 pub use self::send_venue::*;
 pub use self::send_video::*;
+pub use self::set_webhook::*;
 pub use self::stop_message_live_location::*;
 pub use self::stop_poll::*;
 pub use self::unban_chat_member::*;
@@ -11,6 +11,7 @@ pub struct SendAudio<'c, 'p, 't> {
     audio: InputFile,
     caption: Option<Cow<'c, str>>,
     parse_mode: Option<ParseMode>,
+    caption_entities: Option<Vec<MessageEntity>>,
     duration: Option<Integer>,
     performer: Option<Cow<'p, str>>,
     title: Option<Cow<'t, str>>,
@@ -28,6 +29,7 @@ impl<'c, 'p, 't> ToMultipart for SendAudio<'c, 'p, 't> {
             (audio (raw));
             (caption (text), optional);
             (parse_mode (text), optional);
+            (caption_entities (json), optional);
             (duration (text), optional);
             (performer (text), optional);
             (title (text), optional);
@@ -59,6 +61,7 @@ impl<'c, 'p, 't> SendAudio<'c, 'p, 't> {
             audio: audio.into(),
             caption: None,
             parse_mode: None,
+            caption_entities: None,
             duration: None,
             performer: None,
             title: None,
@@ -82,11 +85,22 @@ impl<'c, 'p, 't> SendAudio<'c, 'p, 't> {
         T: Into<Cow<'c, str>>,
     {
         self.caption = Some(caption.into());
+        self.caption_entities = None;
+        self
+    }
+
+    /// Set the caption with explicit entities instead of a parse mode.
+    pub fn rich_caption(&mut self, caption: &RichText) -> &mut Self {
+        let (caption, entities) = caption.to_entities();
+        self.caption = Some(caption.into());
+        self.caption_entities = Some(entities);
+        self.parse_mode = None;
         self
     }
 
     pub fn parse_mode(&mut self, parse_mode: ParseMode) -> &mut Self {
         self.parse_mode = Some(parse_mode);
+        self.caption_entities = None;
         self
     }
 
@@ -13,6 +13,7 @@ pub struct SendDocument<'c> {
     thumb: Option<InputFile>,
     caption: Option<Cow<'c, str>>,
     parse_mode: Option<ParseMode>,
+    caption_entities: Option<Vec<MessageEntity>>,
     reply_to_message_id: Option<MessageId>,
     disable_notification: bool,
     reply_markup: Option<ReplyMarkup>,
@@ -27,6 +28,7 @@ impl<'c> ToMultipart for SendDocument<'c> {
             (thumb (raw), optional);
             (caption (text), optional);
             (parse_mode (text), optional);
+            (caption_entities (json), optional);
             (reply_to_message_id (text), optional);
             (disable_notification (text), when_true);
             (reply_markup (json), optional);
@@ -55,6 +57,7 @@ impl<'c> SendDocument<'c> {
             thumb: None,
             caption: None,
             parse_mode: None,
+            caption_entities: None,
             reply_to_message_id: None,
             reply_markup: None,
             disable_notification: false,
@@ -74,11 +77,22 @@ impl<'c> SendDocument<'c> {
         T: Into<Cow<'c, str>>,
     {
         self.caption = Some(caption.into());
+        self.caption_entities = None;
+        self
+    }
+
+    /// Set the caption with explicit entities instead of a parse mode.
+    pub fn rich_caption(&mut self, caption: &RichText) -> &mut Self {
+        let (caption, entities) = caption.to_entities();
+        self.caption = Some(caption.into());
+        self.caption_entities = Some(entities);
+        self.parse_mode = None;
         self
     }
 
     pub fn parse_mode(&mut self, parse_mode: ParseMode) -> &mut Self {
         self.parse_mode = Some(parse_mode);
+        self.caption_entities = None;
         self
     }
 
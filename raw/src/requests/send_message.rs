@@ -12,6 +12,8 @@ pub struct SendMessage<'s> {
     text: Cow<'s, str>,
     #[serde(skip_serializing_if = "Option::is_none")]
     parse_mode: Option<ParseMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    entities: Option<Vec<MessageEntity>>,
     #[serde(skip_serializing_if = "Not::not")]
     disable_web_page_preview: bool,
     #[serde(skip_serializing_if = "Not::not")]
@@ -41,6 +43,7 @@ impl<'s> SendMessage<'s> {
             chat_id: chat.to_chat_ref(),
             text: text.into(),
             parse_mode: None,
+            entities: None,
             disable_web_page_preview: false,
             disable_notification: false,
             reply_to_message_id: None,
@@ -48,8 +51,18 @@ impl<'s> SendMessage<'s> {
         }
     }
 
+    /// Set the text with explicit entities instead of a parse mode.
+    pub fn rich_text(&mut self, text: &RichText) -> &mut Self {
+        let (text, entities) = text.to_entities();
+        self.text = text.into();
+        self.entities = Some(entities);
+        self.parse_mode = None;
+        self
+    }
+
     pub fn parse_mode(&mut self, parse_mode: ParseMode) -> &mut Self {
         self.parse_mode = Some(parse_mode);
+        self.entities = None;
         self
     }
 
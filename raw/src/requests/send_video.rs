@@ -11,6 +11,7 @@ pub struct SendVideo<'c> {
     video: InputFile,
     caption: Option<Cow<'c, str>>,
     parse_mode: Option<ParseMode>,
+    caption_entities: Option<Vec<MessageEntity>>,
     duration: Option<Integer>,
     width: Option<Integer>,
     height: Option<Integer>,
@@ -29,6 +30,7 @@ impl<'c> ToMultipart for SendVideo<'c> {
             (video (raw));
             (caption (text), optional);
             (parse_mode (text), optional);
+            (caption_entities (json), optional);
             (duration (text), optional);
             (width (text), optional);
             (height (text), optional);
@@ -61,6 +63,7 @@ impl<'c> SendVideo<'c> {
             video: video.into(),
             caption: None,
             parse_mode: None,
+            caption_entities: None,
             duration: None,
             width: None,
             height: None,
@@ -85,11 +88,22 @@ impl<'c> SendVideo<'c> {
         T: Into<Cow<'c, str>>,
     {
         self.caption = Some(caption.into());
+        self.caption_entities = None;
+        self
+    }
+
+    /// Set the caption with explicit entities instead of a parse mode.
+    pub fn rich_caption(&mut self, caption: &RichText) -> &mut Self {
+        let (caption, entities) = caption.to_entities();
+        self.caption = Some(caption.into());
+        self.caption_entities = Some(entities);
+        self.parse_mode = None;
         self
     }
 
     pub fn parse_mode(&mut self, parse_mode: ParseMode) -> &mut Self {
         self.parse_mode = Some(parse_mode);
+        self.caption_entities = None;
         self
     }
 
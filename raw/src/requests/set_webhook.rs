@@ -0,0 +1,96 @@
+use std::borrow::Cow;
+
+use crate::requests::*;
+use crate::types::*;
+
+/// Use this method to specify a url and receive incoming updates via an outgoing webhook.
+/// Whenever there is an update for the bot, Telegram will send an HTTPS POST request
+/// to the specified url, containing a JSON-serialized Update.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+#[must_use = "requests do nothing unless sent"]
+pub struct SetWebhook<'u, 's> {
+    url: Cow<'u, str>,
+    certificate: Option<InputFile>,
+    max_connections: Option<Integer>,
+    allowed_updates: Option<Vec<AllowedUpdate>>,
+    drop_pending_updates: bool,
+    secret_token: Option<Cow<'s, str>>,
+}
+
+impl<'u, 's> ToMultipart for SetWebhook<'u, 's> {
+    fn to_multipart(&self) -> Result<Multipart, Error> {
+        multipart_map! {
+            self,
+            (url (text));
+            (certificate (raw), optional);
+            (max_connections (text), optional);
+            (allowed_updates (json), optional);
+            (drop_pending_updates (text), when_true);
+            (secret_token (text), optional);
+        }
+    }
+}
+
+impl<'u, 's> Request for SetWebhook<'u, 's> {
+    type Type = MultipartRequestType<Self>;
+    type Response = JsonTrueToUnitResponse;
+
+    fn serialize(&self) -> Result<HttpRequest, Error> {
+        Self::Type::serialize(RequestUrl::method("setWebhook"), self)
+    }
+}
+
+impl<'u, 's> SetWebhook<'u, 's> {
+    pub fn new<U>(url: U) -> Self
+    where
+        U: Into<Cow<'u, str>>,
+    {
+        Self {
+            url: url.into(),
+            certificate: None,
+            max_connections: None,
+            allowed_updates: None,
+            drop_pending_updates: false,
+            secret_token: None,
+        }
+    }
+
+    /// Upload your public key certificate so that the root certificate in use can be checked.
+    pub fn certificate<V>(&mut self, certificate: V) -> &mut Self
+    where
+        V: Into<InputFileUpload>,
+    {
+        self.certificate = Some(certificate.into().into());
+        self
+    }
+
+    /// Maximum allowed number of simultaneous HTTPS connections to the webhook
+    /// for update delivery, 1-100. Defaults to 40.
+    pub fn max_connections(&mut self, max_connections: Integer) -> &mut Self {
+        self.max_connections = Some(max_connections);
+        self
+    }
+
+    /// List the types of updates you want your bot to receive.
+    /// Specify an empty list to receive all updates regardless of type.
+    pub fn allowed_updates(&mut self, updates: &[AllowedUpdate]) -> &mut Self {
+        self.allowed_updates = Some(updates.to_vec());
+        self
+    }
+
+    /// Drop all pending updates.
+    pub fn drop_pending_updates(&mut self) -> &mut Self {
+        self.drop_pending_updates = true;
+        self
+    }
+
+    /// A secret token to be sent in a header “X-Telegram-Bot-Api-Secret-Token” in every
+    /// webhook request, 1-256 characters. Only characters A-Z, a-z, 0-9, _ and - are allowed.
+    pub fn secret_token<T>(&mut self, secret_token: T) -> &mut Self
+    where
+        T: Into<Cow<'s, str>>,
+    {
+        self.secret_token = Some(secret_token.into());
+        self
+    }
+}
@@ -1,4 +1,7 @@
+use std::ops::Range;
+
 use serde::de::{Deserialize, Deserializer, Error};
+use serde::ser::{Serialize, Serializer};
 
 use crate::types::*;
 use crate::url::*;
@@ -644,6 +647,33 @@ pub struct MessageEntity {
     pub kind: MessageEntityKind,
 }
 
+impl MessageEntity {
+    /// Byte range of the entity in the `text` it belongs to, converted from UTF-16 offsets.
+    /// Returns `None` if the entity doesn't fit the text or splits a character.
+    pub fn range(&self, text: &str) -> Option<Range<usize>> {
+        let (start, end) = (self.offset, self.offset + self.length);
+        let mut position = 0;
+        let mut range = None;
+
+        for (index, ch) in text.char_indices().chain(Some((text.len(), '\0'))) {
+            if position == start {
+                range = Some(index..index);
+            }
+            if position == end {
+                return range.map(|range| range.start..index);
+            }
+            position += ch.len_utf16() as Integer;
+        }
+
+        None
+    }
+
+    /// Substring of the `text` the entity covers.
+    pub fn text<'a>(&self, text: &'a str) -> Option<&'a str> {
+        self.range(text).map(|range| &text[range])
+    }
+}
+
 /// Kind of the entity.
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub enum MessageEntityKind {
@@ -654,8 +684,13 @@ pub enum MessageEntityKind {
     Email,
     Bold,
     Italic,
+    Underline,
+    Strikethrough,
+    Spoiler,
     Code,
     Pre,
+    /// Monowidth block of code in the programming language.
+    PreCode(String),
     TextLink(String), // TODO(knsd) URL?
     TextMention(User),
     #[doc(hidden)]
@@ -691,8 +726,14 @@ impl<'de> Deserialize<'de> for MessageEntity {
             "email" => Email,
             "bold" => Bold,
             "italic" => Italic,
+            "underline" => Underline,
+            "strikethrough" => Strikethrough,
+            "spoiler" => Spoiler,
             "code" => Code,
-            "pre" => Pre,
+            "pre" => match raw.language {
+                Some(language) => PreCode(language),
+                None => Pre,
+            },
             "text_link" => TextLink(required_field!(url)),
             "text_mention" => TextMention(required_field!(user)),
             _ => Unknown(raw),
@@ -706,12 +747,57 @@ impl<'de> Deserialize<'de> for MessageEntity {
     }
 }
 
+impl Serialize for MessageEntity {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        use self::MessageEntityKind::*;
+
+        let (type_, url, user, language) = match self.kind {
+            Mention => ("mention", None, None, None),
+            Hashtag => ("hashtag", None, None, None),
+            BotCommand => ("bot_command", None, None, None),
+            Url => ("url", None, None, None),
+            Email => ("email", None, None, None),
+            Bold => ("bold", None, None, None),
+            Italic => ("italic", None, None, None),
+            Underline => ("underline", None, None, None),
+            Strikethrough => ("strikethrough", None, None, None),
+            Spoiler => ("spoiler", None, None, None),
+            Code => ("code", None, None, None),
+            Pre => ("pre", None, None, None),
+            PreCode(ref language) => ("pre", None, None, Some(language.clone())),
+            TextLink(ref url) => ("text_link", Some(url.clone()), None, None),
+            TextMention(ref user) => ("text_mention", None, Some(user.clone()), None),
+            Unknown(ref raw) => (
+                raw.type_.as_str(),
+                raw.url.clone(),
+                raw.user.clone(),
+                raw.language.clone(),
+            ),
+        };
+
+        RawMessageEntity {
+            type_: type_.to_string(),
+            offset: self.offset,
+            length: self.length,
+            url,
+            user,
+            language,
+        }
+        .serialize(serializer)
+    }
+}
+
 /// This object represents one special entity in a text message.
 /// For example, hashtags, usernames, URLs, etc. Directly mapped.
-#[derive(Debug, Clone, PartialEq, PartialOrd, Deserialize)]
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct RawMessageEntity {
     /// Type of the entity. Can be mention (@username), hashtag, bot_command, url, email,
-    /// bold (bold text), italic (italic text), code (monowidth string), pre (monowidth block),
+    /// bold (bold text), italic (italic text), underline (underlined text),
+    /// strikethrough (strikethrough text), spoiler (spoiler message),
+    /// code (monowidth string), pre (monowidth block),
     /// text_link (for clickable text URLs), text_mention (for users without usernames).
     #[serde(rename = "type")]
     pub type_: String,
@@ -720,9 +806,14 @@ pub struct RawMessageEntity {
     /// Length of the entity in UTF-16 code units.
     pub length: Integer,
     /// For “text_link” only, url that will be opened after user taps on the text.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub url: Option<String>,
     /// For “text_mention” only, the mentioned user.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub user: Option<User>,
+    /// For “pre” only, the programming language of the entity text.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
 }
 
 /// This object represents one size of a photo or a file / sticker thumbnail.
@@ -958,10 +1049,16 @@ pub struct File {
 }
 
 impl File {
+    /// Obtains URL of the file on the default Bot API server.
     pub fn get_url(&self, token: &str) -> Option<String> {
+        self.get_url_with(&ApiUrl::default(), token)
+    }
+
+    /// Obtains URL of the file on the Bot API server with `api_url`.
+    pub fn get_url_with(&self, api_url: &ApiUrl, token: &str) -> Option<String> {
         self.file_path
             .as_ref()
-            .map(|path| format!("{}file/bot{}/{}", telegram_api_url(), token, path))
+            .map(|path| api_url.file_url(token, path))
     }
 }
 
@@ -9,8 +9,10 @@ pub mod primitive;
 pub mod refs;
 pub mod reply_markup;
 pub mod response_parameters;
+pub mod rich_text;
 pub mod text;
 pub mod update;
+pub mod webhook_info;
 
 pub use self::callback_query::*;
 pub use self::chat::*;
@@ -23,5 +25,7 @@ pub use self::primitive::*;
 pub use self::refs::*;
 pub use self::reply_markup::*;
 pub use self::response_parameters::*;
+pub use self::rich_text::*;
 pub use self::text::*;
 pub use self::update::*;
+pub use self::webhook_info::*;
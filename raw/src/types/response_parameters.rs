@@ -12,6 +12,8 @@ pub enum ResponseWrapper<T> {
     },
     /// Request was unsuccessful.
     Error {
+        /// Error code, its meaning is subject to change in the future.
+        error_code: Option<Integer>,
         /// Human-readable description of the result.
         description: String,
         /// Contains information about why a request was unsuccessful.
@@ -27,10 +29,12 @@ impl<'de, T: Deserialize<'de>> Deserialize<'de> for ResponseWrapper<T> {
         let raw: RawResponse<T> = Deserialize::deserialize(deserializer)?;
         match (raw.ok, raw.description, raw.result) {
             (false, Some(description), None) => Ok(ResponseWrapper::Error {
+                error_code: raw.error_code,
                 description: description,
                 parameters: raw.parameters,
             }),
-            (true, None, Some(result)) => Ok(ResponseWrapper::Success { result: result }),
+            // Successful responses of some methods, e.g. setWebhook, include a description.
+            (true, _, Some(result)) => Ok(ResponseWrapper::Success { result: result }),
             _ => Err(D::Error::custom("ambiguous response")),
         }
     }
@@ -41,6 +45,8 @@ impl<'de, T: Deserialize<'de>> Deserialize<'de> for ResponseWrapper<T> {
 pub struct RawResponse<T> {
     /// If ‘ok’ equals true, the request was successful.
     ok: bool,
+    /// Error code in case of unsuccessful request.
+    error_code: Option<Integer>,
     /// Human-readable description of the result.
     description: Option<String>,
     /// Result of the query.
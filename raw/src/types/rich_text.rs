@@ -0,0 +1,594 @@
+use std::cmp::Reverse;
+use std::fmt::Write;
+use std::ops::Range;
+
+use crate::types::*;
+
+/// Maximum length of a message text in UTF-16 code units, after parsing of entities.
+pub const MESSAGE_TEXT_MAX_LENGTH: usize = 4096;
+
+/// Formatted text of a message, composed from plain text and entities.
+///
+/// The text can be rendered to MarkdownV2 or HTML markup with all user input escaped,
+/// or to the plain text with an explicit list of entities, which doesn't require
+/// any escaping at all. Requests accept it directly, e.g. using
+/// [`SendMessage::rich_text`](../requests/struct.SendMessage.html#method.rich_text).
+/// Received texts are converted back with [`from_entities`](#method.from_entities).
+///
+/// # Examples
+///
+/// ```rust
+/// use telegram_bot_raw::RichText;
+///
+/// let mut name = RichText::new();
+/// name.plain("John ").italic("<Doe>");
+///
+/// let mut text = RichText::new();
+/// text.plain("Hello, ").bold(name).plain("! See ").link("docs", "https://core.telegram.org");
+///
+/// assert_eq!(
+///     text.to_html(),
+///     r#"Hello, <b>John <i>&lt;Doe&gt;</i></b>! See <a href="https://core.telegram.org">docs</a>"#
+/// );
+/// assert_eq!(
+///     text.to_markdown_v2(),
+///     r"Hello, *John _<Doe\>_*\! See [docs](https://core.telegram.org)"
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd)]
+pub struct RichText {
+    nodes: Vec<RichTextNode>,
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+enum RichTextNode {
+    Plain(String),
+    Entity(MessageEntityKind, RichText),
+}
+
+impl RichText {
+    /// Create a new empty `RichText` instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a rich text from a received `text` and its `entities`, e.g. of a
+    /// `MessageKind::Text`, to render it back to markup. Entities which partially
+    /// overlap are split, so they nest properly; entities not fitting the text are skipped.
+    pub fn from_entities(text: &str, entities: &[MessageEntity]) -> Self {
+        let mut ranges: Vec<_> = entities
+            .iter()
+            .filter_map(|entity| {
+                let range = entity.range(text)?;
+                if range.start == range.end {
+                    return None;
+                }
+                Some((range, entity.kind.clone()))
+            })
+            .collect();
+        sort_ranges(&mut ranges);
+        nest(text, 0..text.len(), ranges)
+    }
+
+    /// Append plain text.
+    pub fn plain<T: Into<String>>(&mut self, text: T) -> &mut Self {
+        self.nodes.push(RichTextNode::Plain(text.into()));
+        self
+    }
+
+    /// Append bold text.
+    pub fn bold<T: Into<RichText>>(&mut self, text: T) -> &mut Self {
+        self.entity(MessageEntityKind::Bold, text)
+    }
+
+    /// Append italic text.
+    pub fn italic<T: Into<RichText>>(&mut self, text: T) -> &mut Self {
+        self.entity(MessageEntityKind::Italic, text)
+    }
+
+    /// Append underlined text.
+    pub fn underline<T: Into<RichText>>(&mut self, text: T) -> &mut Self {
+        self.entity(MessageEntityKind::Underline, text)
+    }
+
+    /// Append strikethrough text.
+    pub fn strikethrough<T: Into<RichText>>(&mut self, text: T) -> &mut Self {
+        self.entity(MessageEntityKind::Strikethrough, text)
+    }
+
+    /// Append text hidden as a spoiler.
+    pub fn spoiler<T: Into<RichText>>(&mut self, text: T) -> &mut Self {
+        self.entity(MessageEntityKind::Spoiler, text)
+    }
+
+    /// Append monowidth string.
+    pub fn code<T: Into<String>>(&mut self, text: T) -> &mut Self {
+        self.entity(MessageEntityKind::Code, text.into())
+    }
+
+    /// Append monowidth block.
+    pub fn pre<T: Into<String>>(&mut self, text: T) -> &mut Self {
+        self.entity(MessageEntityKind::Pre, text.into())
+    }
+
+    /// Append monowidth block of code in the programming `language`.
+    pub fn pre_with_language<T: Into<String>, L: Into<String>>(
+        &mut self,
+        text: T,
+        language: L,
+    ) -> &mut Self {
+        self.entity(MessageEntityKind::PreCode(language.into()), text.into())
+    }
+
+    /// Append text which opens the `url` when clicked.
+    pub fn link<T: Into<RichText>, U: Into<String>>(&mut self, text: T, url: U) -> &mut Self {
+        self.entity(MessageEntityKind::TextLink(url.into()), text)
+    }
+
+    /// Append text which mentions the `user`, also users without usernames.
+    pub fn text_mention<T: Into<RichText>>(&mut self, text: T, user: User) -> &mut Self {
+        self.entity(MessageEntityKind::TextMention(user), text)
+    }
+
+    /// Append text with an entity of the `kind`. Entities which can't be
+    /// expressed in markup, e.g. hashtags, are rendered to MarkdownV2 or HTML
+    /// as plain text and left for Telegram to detect.
+    pub fn entity<T: Into<RichText>>(&mut self, kind: MessageEntityKind, text: T) -> &mut Self {
+        self.nodes.push(RichTextNode::Entity(kind, text.into()));
+        self
+    }
+
+    /// Append another rich text.
+    pub fn append(&mut self, text: RichText) -> &mut Self {
+        self.nodes.extend(text.nodes);
+        self
+    }
+
+    /// Returns `true` if the text is empty.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.iter().all(|node| match node {
+            RichTextNode::Plain(text) => text.is_empty(),
+            RichTextNode::Entity(_, text) => text.is_empty(),
+        })
+    }
+
+    /// Split the text into chunks of at most `max_length` UTF-16 code units,
+    /// e.g. `MESSAGE_TEXT_MAX_LENGTH`, preferably on paragraph, line or word
+    /// boundaries outside of entities. Whitespace at the boundaries is dropped,
+    /// entities which have to be split continue in the next chunk and keep
+    /// the whitespace they were split on, so code blocks stay intact.
+    pub fn split(&self, max_length: usize) -> Vec<RichText> {
+        let (text, entities) = self.to_entities();
+        let ranges: Vec<_> = entities
+            .iter()
+            .filter_map(|entity| Some((entity.range(&text)?, entity.kind.clone())))
+            .collect();
+
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        while start < text.len() {
+            let limit = utf16_limit(&text, start, max_length);
+            let (end, next) = if limit == text.len() {
+                (limit, limit)
+            } else {
+                split_point(&text, start..limit, &ranges)
+            };
+            if !text[start..end].trim().is_empty() {
+                chunks.push(chunk(&text, start..end, &ranges));
+            }
+            start = next;
+        }
+        chunks
+    }
+
+    /// Render to the plain text without any formatting.
+    pub fn to_plain(&self) -> String {
+        let mut result = String::new();
+        self.write_plain(&mut result);
+        result
+    }
+
+    /// Render to the plain text and the list of entities with offsets in UTF-16 code units.
+    pub fn to_entities(&self) -> (String, Vec<MessageEntity>) {
+        let mut text = String::new();
+        let mut entities = Vec::new();
+        self.write_entities(&mut text, &mut 0, &mut entities);
+        (text, entities)
+    }
+
+    /// Render to the markup for `ParseMode::Html`.
+    pub fn to_html(&self) -> String {
+        let mut result = String::new();
+        self.write_html(&mut result);
+        result
+    }
+
+    /// Render to the markup for `ParseMode::MarkdownV2`.
+    pub fn to_markdown_v2(&self) -> String {
+        let mut result = String::new();
+        self.write_markdown_v2(&mut result);
+        result
+    }
+
+    fn write_plain(&self, result: &mut String) {
+        for node in &self.nodes {
+            match node {
+                RichTextNode::Plain(text) => result.push_str(text),
+                RichTextNode::Entity(_, text) => text.write_plain(result),
+            }
+        }
+    }
+
+    fn write_entities(
+        &self,
+        result: &mut String,
+        offset: &mut Integer,
+        entities: &mut Vec<MessageEntity>,
+    ) {
+        for node in &self.nodes {
+            match node {
+                RichTextNode::Plain(text) => {
+                    result.push_str(text);
+                    *offset += utf16_length(text);
+                }
+                RichTextNode::Entity(kind, text) => {
+                    let start = *offset;
+                    let index = entities.len();
+                    entities.push(MessageEntity {
+                        offset: start,
+                        length: 0,
+                        kind: kind.clone(),
+                    });
+                    text.write_entities(result, offset, entities);
+                    entities[index].length = *offset - start;
+                }
+            }
+        }
+    }
+
+    fn write_html(&self, result: &mut String) {
+        for node in &self.nodes {
+            let (kind, text) = match node {
+                RichTextNode::Plain(text) => {
+                    escape_html(result, text);
+                    continue;
+                }
+                RichTextNode::Entity(kind, text) => (kind, text),
+            };
+            match kind {
+                MessageEntityKind::Bold => {
+                    result.push_str("<b>");
+                    text.write_html(result);
+                    result.push_str("</b>");
+                }
+                MessageEntityKind::Italic => {
+                    result.push_str("<i>");
+                    text.write_html(result);
+                    result.push_str("</i>");
+                }
+                MessageEntityKind::Underline => {
+                    result.push_str("<u>");
+                    text.write_html(result);
+                    result.push_str("</u>");
+                }
+                MessageEntityKind::Strikethrough => {
+                    result.push_str("<s>");
+                    text.write_html(result);
+                    result.push_str("</s>");
+                }
+                MessageEntityKind::Spoiler => {
+                    result.push_str("<tg-spoiler>");
+                    text.write_html(result);
+                    result.push_str("</tg-spoiler>");
+                }
+                MessageEntityKind::Code => {
+                    result.push_str("<code>");
+                    escape_html(result, &text.to_plain());
+                    result.push_str("</code>");
+                }
+                MessageEntityKind::Pre => {
+                    result.push_str("<pre>");
+                    escape_html(result, &text.to_plain());
+                    result.push_str("</pre>");
+                }
+                MessageEntityKind::PreCode(language) => {
+                    result.push_str("<pre><code class=\"language-");
+                    escape_html(result, language);
+                    result.push_str("\">");
+                    escape_html(result, &text.to_plain());
+                    result.push_str("</code></pre>");
+                }
+                MessageEntityKind::TextLink(url) => {
+                    result.push_str("<a href=\"");
+                    escape_html(result, url);
+                    result.push_str("\">");
+                    text.write_html(result);
+                    result.push_str("</a>");
+                }
+                MessageEntityKind::TextMention(user) => {
+                    let _ = write!(result, "<a href=\"tg://user?id={}\">", user.id);
+                    text.write_html(result);
+                    result.push_str("</a>");
+                }
+                _ => text.write_html(result),
+            }
+        }
+    }
+
+    fn write_markdown_v2(&self, result: &mut String) {
+        for node in &self.nodes {
+            let (kind, text) = match node {
+                RichTextNode::Plain(text) => {
+                    escape_markdown_v2(result, text, MARKDOWN_V2_SPECIAL);
+                    continue;
+                }
+                RichTextNode::Entity(kind, text) => (kind, text),
+            };
+            match kind {
+                MessageEntityKind::Bold => {
+                    result.push('*');
+                    text.write_markdown_v2(result);
+                    result.push('*');
+                }
+                MessageEntityKind::Italic => {
+                    push_underscores(result, "_");
+                    text.write_markdown_v2(result);
+                    push_underscores(result, "_");
+                }
+                MessageEntityKind::Underline => {
+                    push_underscores(result, "__");
+                    text.write_markdown_v2(result);
+                    push_underscores(result, "__");
+                }
+                MessageEntityKind::Strikethrough => {
+                    result.push('~');
+                    text.write_markdown_v2(result);
+                    result.push('~');
+                }
+                MessageEntityKind::Spoiler => {
+                    result.push_str("||");
+                    text.write_markdown_v2(result);
+                    result.push_str("||");
+                }
+                MessageEntityKind::Code => {
+                    result.push('`');
+                    escape_markdown_v2(result, &text.to_plain(), MARKDOWN_V2_CODE_SPECIAL);
+                    result.push('`');
+                }
+                MessageEntityKind::Pre => {
+                    result.push_str("```\n");
+                    escape_markdown_v2(result, &text.to_plain(), MARKDOWN_V2_CODE_SPECIAL);
+                    result.push_str("\n```");
+                }
+                MessageEntityKind::PreCode(language) => {
+                    result.push_str("```");
+                    escape_markdown_v2(result, language, MARKDOWN_V2_CODE_SPECIAL);
+                    result.push('\n');
+                    escape_markdown_v2(result, &text.to_plain(), MARKDOWN_V2_CODE_SPECIAL);
+                    result.push_str("\n```");
+                }
+                MessageEntityKind::TextLink(url) => {
+                    result.push('[');
+                    text.write_markdown_v2(result);
+                    result.push_str("](");
+                    escape_markdown_v2(result, url, MARKDOWN_V2_URL_SPECIAL);
+                    result.push(')');
+                }
+                MessageEntityKind::TextMention(user) => {
+                    result.push('[');
+                    text.write_markdown_v2(result);
+                    let _ = write!(result, "](tg://user?id={})", user.id);
+                }
+                _ => text.write_markdown_v2(result),
+            }
+        }
+    }
+}
+
+impl<'a> From<&'a str> for RichText {
+    fn from(value: &'a str) -> Self {
+        value.to_string().into()
+    }
+}
+
+impl From<String> for RichText {
+    fn from(value: String) -> Self {
+        RichText {
+            nodes: vec![RichTextNode::Plain(value)],
+        }
+    }
+}
+
+impl<'a> From<&'a RichText> for RichText {
+    fn from(value: &'a RichText) -> Self {
+        value.clone()
+    }
+}
+
+impl<'a> From<&'a mut RichText> for RichText {
+    fn from(value: &'a mut RichText) -> Self {
+        value.clone()
+    }
+}
+
+fn utf16_length(text: &str) -> Integer {
+    text.encode_utf16().count() as Integer
+}
+
+/// End of the longest part of `text` from `start` which fits `max_length` UTF-16 code units,
+/// at least one character long.
+fn utf16_limit(text: &str, start: usize, max_length: usize) -> usize {
+    let mut length = 0;
+    for (index, ch) in text[start..].char_indices() {
+        length += ch.len_utf16();
+        if length > max_length && index > 0 {
+            return start + index;
+        }
+    }
+    text.len()
+}
+
+/// Choose where to split `text` inside `range`, returns the end of the chunk
+/// and the start of the next one, which differ by the dropped separator.
+/// Separators inside entities are kept at the end of the chunk if they fit.
+fn split_point(
+    text: &str,
+    range: Range<usize>,
+    ranges: &[(Range<usize>, MessageEntityKind)],
+) -> (usize, usize) {
+    // Separators may extend past the end of the range, they are dropped anyway.
+    let window_end = text[range.end..]
+        .char_indices()
+        .nth(2)
+        .map_or(text.len(), |(index, _)| range.end + index);
+    let window = &text[range.start..window_end];
+    let paragraphs = window.match_indices("\n\n").map(|(index, _)| (index, 2));
+    let lines = window.match_indices('\n').map(|(index, _)| (index, 1));
+    let words = window
+        .char_indices()
+        .filter(|(_, ch)| ch.is_whitespace())
+        .map(|(index, ch)| (index, ch.len_utf8()));
+    let candidates: Vec<Vec<(usize, usize)>> =
+        vec![paragraphs.collect(), lines.collect(), words.collect()];
+
+    let inside_entity = |&(end, next): &(usize, usize)| {
+        ranges
+            .iter()
+            .any(|(entity, _)| entity.start < next && end < entity.end)
+    };
+    for &outside_only in &[true, false] {
+        for tier in &candidates {
+            let point = tier
+                .iter()
+                .rev()
+                .map(|&(index, length)| (range.start + index, range.start + index + length))
+                .find(|point| {
+                    range.start < point.0
+                        && point.0 <= range.end
+                        && if outside_only {
+                            !inside_entity(point)
+                        } else {
+                            // Whitespace only chunks are dropped, so they can't hold entity text.
+                            !text[range.start..point.0].trim().is_empty()
+                        }
+                });
+            if let Some((end, next)) = point {
+                if outside_only || !inside_entity(&(end, next)) {
+                    return (end, next);
+                }
+                // The separator is a part of the entity text, it must not be dropped.
+                return if next <= range.end {
+                    (next, next)
+                } else {
+                    (end, end)
+                };
+            }
+        }
+    }
+    (range.end, range.end)
+}
+
+/// Part of `text` inside `range` with the entities clipped to it.
+fn chunk(
+    text: &str,
+    range: Range<usize>,
+    ranges: &[(Range<usize>, MessageEntityKind)],
+) -> RichText {
+    let entities: Vec<_> = ranges
+        .iter()
+        .filter_map(|(entity, kind)| {
+            let start = entity.start.max(range.start);
+            let end = entity.end.min(range.end);
+            if start >= end {
+                return None;
+            }
+            Some(MessageEntity {
+                offset: utf16_length(&text[range.start..start]),
+                length: utf16_length(&text[start..end]),
+                kind: kind.clone(),
+            })
+        })
+        .collect();
+    RichText::from_entities(&text[range], &entities)
+}
+
+fn sort_ranges(ranges: &mut [(Range<usize>, MessageEntityKind)]) {
+    // Outer entities go first, the sort is stable, so equal ranges keep their order.
+    ranges.sort_by_key(|(range, _)| (range.start, Reverse(range.end)));
+}
+
+/// Build the tree of `ranges` sorted with `sort_ranges`, all of them inside `range`.
+fn nest(
+    text: &str,
+    range: Range<usize>,
+    mut ranges: Vec<(Range<usize>, MessageEntityKind)>,
+) -> RichText {
+    let mut result = RichText::new();
+    let mut position = range.start;
+
+    while !ranges.is_empty() {
+        let (outer, kind) = ranges.remove(0);
+        let mut children = Vec::new();
+        let mut rest = Vec::new();
+        for (inner, inner_kind) in ranges {
+            if inner.start >= outer.end {
+                rest.push((inner, inner_kind));
+            } else if inner.end <= outer.end {
+                children.push((inner, inner_kind));
+            } else {
+                children.push((inner.start..outer.end, inner_kind.clone()));
+                rest.push((outer.end..inner.end, inner_kind));
+            }
+        }
+        sort_ranges(&mut rest);
+        ranges = rest;
+
+        if position < outer.start {
+            result.plain(&text[position..outer.start]);
+        }
+        position = outer.end;
+        result.entity(kind, nest(text, outer, children));
+    }
+
+    if position < range.end {
+        result.plain(&text[position..range.end]);
+    }
+    result
+}
+
+const MARKDOWN_V2_SPECIAL: &str = "_*[]()~`>#+-=|{}.!\\";
+const MARKDOWN_V2_CODE_SPECIAL: &str = "`\\";
+const MARKDOWN_V2_URL_SPECIAL: &str = ")\\";
+
+fn escape_markdown_v2(result: &mut String, text: &str, special: &str) {
+    for ch in text.chars() {
+        if special.contains(ch) {
+            result.push('\\');
+        }
+        result.push(ch);
+    }
+}
+
+/// Push italic or underline `marker`, separating it from an adjacent one with `\r`,
+/// which Telegram ignores, as `___` is ambiguous.
+fn push_underscores(result: &mut String, marker: &str) {
+    if let Some(rest) = result.strip_suffix('_') {
+        let escapes = rest.chars().rev().take_while(|&ch| ch == '\\').count();
+        if escapes % 2 == 0 {
+            result.push('\r');
+        }
+    }
+    result.push_str(marker);
+}
+
+fn escape_html(result: &mut String, text: &str) {
+    for ch in text.chars() {
+        match ch {
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '&' => result.push_str("&amp;"),
+            '"' => result.push_str("&quot;"),
+            ch => result.push(ch),
+        }
+    }
+}
@@ -10,3 +10,66 @@ pub fn telegram_api_url() -> String {
         Err(_) => String::from(TELEGRAM_API_URL_DEFAULT),
     }
 }
+
+/// Base URLs of the Telegram Bot API server used for requests and file downloads.
+///
+/// Default URLs are obtained from `telegram_api_url()`.
+#[derive(Debug, Clone, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub struct ApiUrl {
+    base_url: String,
+    file_base_url: String,
+}
+
+impl ApiUrl {
+    /// Create a new `ApiUrl` with the `base_url` of the Bot API server,
+    /// files are downloaded from `<base_url>file/`.
+    pub fn new<T: AsRef<str>>(base_url: T) -> Self {
+        let base_url = with_trailing_slash(base_url.as_ref());
+        let file_base_url = format!("{}file/", base_url);
+        ApiUrl {
+            base_url,
+            file_base_url,
+        }
+    }
+
+    /// Create a new `ApiUrl` with the `base_url` of the Bot API server,
+    /// files are downloaded from `file_base_url`.
+    pub fn with_file_base_url<T: AsRef<str>, F: AsRef<str>>(base_url: T, file_base_url: F) -> Self {
+        ApiUrl {
+            base_url: with_trailing_slash(base_url.as_ref()),
+            file_base_url: with_trailing_slash(file_base_url.as_ref()),
+        }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn file_base_url(&self) -> &str {
+        &self.file_base_url
+    }
+
+    /// Obtains URL of the Bot API `method`.
+    pub fn method_url(&self, token: &str, method: &str) -> String {
+        format!("{}bot{}/{}", self.base_url, token, method)
+    }
+
+    /// Obtains URL of the file with `file_path` for downloading.
+    pub fn file_url(&self, token: &str, file_path: &str) -> String {
+        format!("{}bot{}/{}", self.file_base_url, token, file_path)
+    }
+}
+
+impl Default for ApiUrl {
+    fn default() -> Self {
+        ApiUrl::new(telegram_api_url())
+    }
+}
+
+fn with_trailing_slash(url: &str) -> String {
+    if url.ends_with('/') {
+        url.to_string()
+    } else {
+        format!("{}/", url)
+    }
+}
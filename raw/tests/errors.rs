@@ -0,0 +1,88 @@
+use telegram_bot_raw::{ApiError, Error, HttpResponse, JsonIdResponse, ResponseType, True};
+
+fn error(body: &str) -> Error {
+    let response = HttpResponse {
+        status: 400,
+        headers: Vec::new(),
+        body: Some(body.as_bytes().to_vec()),
+    };
+    <JsonIdResponse<True>>::deserialize(response).unwrap_err()
+}
+
+#[test]
+fn api_error() {
+    let cases = vec![
+        (
+            r#"{"ok":false,"error_code":403,"description":"Forbidden: bot was blocked by the user"}"#,
+            ApiError::BotBlockedByUser,
+        ),
+        (
+            r#"{"ok":false,"error_code":400,"description":"Bad Request: chat not found"}"#,
+            ApiError::ChatNotFound,
+        ),
+        (
+            r#"{"ok":false,"error_code":400,"description":"Bad Request: message is not modified: specified new message content and reply markup are exactly the same as a current content and reply markup of the message"}"#,
+            ApiError::MessageNotModified,
+        ),
+        (
+            r#"{"ok":false,"error_code":400,"description":"Bad Request: message to edit not found"}"#,
+            ApiError::MessageToEditNotFound,
+        ),
+        (
+            r#"{"ok":false,"error_code":429,"description":"Too Many Requests: retry after 5","parameters":{"retry_after":5}}"#,
+            ApiError::TooManyRequests,
+        ),
+        (
+            r#"{"ok":false,"error_code":409,"description":"Conflict: terminated by other getUpdates request"}"#,
+            ApiError::Conflict,
+        ),
+        (
+            r#"{"ok":false,"error_code":401,"description":"Unauthorized"}"#,
+            ApiError::Unauthorized,
+        ),
+        (
+            r#"{"ok":false,"error_code":400,"description":"Bad Request: message text is empty"}"#,
+            ApiError::Other,
+        ),
+        (r#"{"ok":false,"description":"Unknown"}"#, ApiError::Other),
+    ];
+
+    for (body, expected) in cases {
+        assert_eq!(error(body).api_error(), Some(expected), "{}", body);
+    }
+}
+
+#[test]
+fn error_code() {
+    let error =
+        error(r#"{"ok":false,"error_code":400,"description":"Bad Request: chat not found"}"#);
+    assert_eq!(error.error_code(), Some(400));
+    assert_eq!(error.description(), Some("Bad Request: chat not found"));
+
+    let response = HttpResponse {
+        status: 200,
+        headers: Vec::new(),
+        body: None,
+    };
+    let error = <JsonIdResponse<True>>::deserialize(response).unwrap_err();
+    assert_eq!(error.error_code(), None);
+    assert_eq!(error.api_error(), None);
+}
+
+#[test]
+fn unexpected_response() {
+    let response = HttpResponse {
+        status: 502,
+        headers: vec![("content-type".to_string(), "text/html".to_string())],
+        body: Some(b"<html><body>502 Bad Gateway</body></html>\n".to_vec()),
+    };
+    assert_eq!(response.header("Content-Type"), Some("text/html"));
+
+    let error = <JsonIdResponse<True>>::deserialize(response).unwrap_err();
+    assert_eq!(error.unexpected_status(), Some(502));
+    assert_eq!(error.api_error(), None);
+    assert_eq!(
+        error.to_string(),
+        "unexpected response with status 502: <html><body>502 Bad Gateway</body></html>"
+    );
+}
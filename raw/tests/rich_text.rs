@@ -0,0 +1,257 @@
+use serde_json::json;
+
+use telegram_bot_raw::{
+    ChatId, MessageEntity, MessageEntityKind, RichText, SendMessage, User, UserId,
+};
+
+fn user() -> User {
+    User {
+        id: UserId::new(42),
+        first_name: "John".to_string(),
+        last_name: None,
+        username: None,
+        is_bot: false,
+        language_code: None,
+    }
+}
+
+fn text() -> RichText {
+    let mut inner = RichText::new();
+    inner.plain("😀 ").italic("a_b");
+
+    let mut text = RichText::new();
+    text.bold(inner)
+        .plain(" 1+1=2. ")
+        .code("x `y` \\")
+        .plain(" ")
+        .link("<link>", "https://example.com/(a)")
+        .plain(" ")
+        .text_mention("John", user());
+    text
+}
+
+#[test]
+fn markup() {
+    assert_eq!(
+        text().to_markdown_v2(),
+        r"*😀 _a\_b_* 1\+1\=2\. `x \`y\` \\` [<link\>](https://example.com/(a\)) [John](tg://user?id=42)"
+    );
+    assert_eq!(
+        text().to_html(),
+        r#"<b>😀 <i>a_b</i></b> 1+1=2. <code>x `y` \</code> <a href="https://example.com/(a)">&lt;link&gt;</a> <a href="tg://user?id=42">John</a>"#
+    );
+
+    let mut pre = RichText::new();
+    pre.pre("fn main() {}")
+        .entity(MessageEntityKind::Hashtag, "#tag");
+    assert_eq!(pre.to_markdown_v2(), "```\nfn main() {}\n```\\#tag");
+    assert_eq!(pre.to_html(), "<pre>fn main() {}</pre>#tag");
+}
+
+#[test]
+fn formatting() {
+    let mut text = RichText::new();
+    text.underline("u")
+        .plain(" ")
+        .strikethrough("s~")
+        .plain(" ")
+        .spoiler("|x|")
+        .plain(" ")
+        .italic(RichText::from("a_").underline("b"))
+        .plain("\n")
+        .pre_with_language("fn main() {}", "rust");
+    assert_eq!(
+        text.to_markdown_v2(),
+        "__u__ ~s\\~~ ||\\|x\\||| _a\\___b__\r_\n```rust\nfn main() {}\n```"
+    );
+    assert_eq!(
+        text.to_html(),
+        "<u>u</u> <s>s~</s> <tg-spoiler>|x|</tg-spoiler> <i>a_<u>b</u></i>\n\
+         <pre><code class=\"language-rust\">fn main() {}</code></pre>"
+    );
+
+    // Received entities are rendered the same way.
+    let (plain, entities) = text.to_entities();
+    let received: Vec<MessageEntity> =
+        serde_json::from_value(serde_json::to_value(&entities).unwrap()).unwrap();
+    assert_eq!(received, entities);
+    let received = RichText::from_entities(&plain, &received);
+    assert_eq!(received.to_markdown_v2(), text.to_markdown_v2());
+    assert_eq!(received.to_html(), text.to_html());
+}
+
+#[test]
+fn entities() {
+    let (text, entities) = text().to_entities();
+    assert_eq!(text, "😀 a_b 1+1=2. x `y` \\ <link> John");
+
+    let entities: Vec<_> = entities
+        .into_iter()
+        .map(|entity| (entity.offset, entity.length, entity.kind))
+        .collect();
+    assert_eq!(
+        entities,
+        vec![
+            (0, 6, MessageEntityKind::Bold),
+            (3, 3, MessageEntityKind::Italic),
+            (14, 7, MessageEntityKind::Code),
+            (
+                22,
+                6,
+                MessageEntityKind::TextLink("https://example.com/(a)".to_string())
+            ),
+            (29, 4, MessageEntityKind::TextMention(user())),
+        ]
+    );
+}
+
+#[test]
+fn request() {
+    let mut text = RichText::new();
+    text.plain("Hi, ").text_mention("John", user());
+
+    let mut request = SendMessage::new(ChatId::new(1), "");
+    request.rich_text(&text);
+    assert_eq!(
+        serde_json::to_value(&request).unwrap(),
+        json!({
+            "chat_id": 1,
+            "text": "Hi, John",
+            "entities": [{
+                "type": "text_mention",
+                "offset": 4,
+                "length": 4,
+                "user": {"id": 42, "first_name": "John", "is_bot": false},
+            }],
+        })
+    );
+}
+
+#[test]
+fn unknown_entity() {
+    let quote: MessageEntity =
+        serde_json::from_value(json!({"type": "blockquote", "offset": 6, "length": 3})).unwrap();
+    let mut text = RichText::new();
+    text.plain("quote: ")
+        .append(RichText::from_entities("hello abc", &[quote]));
+
+    let mut request = SendMessage::new(ChatId::new(1), "");
+    request.rich_text(&text);
+    assert_eq!(
+        serde_json::to_value(&request).unwrap(),
+        json!({
+            "chat_id": 1,
+            "text": "quote: hello abc",
+            "entities": [{"type": "blockquote", "offset": 13, "length": 3}],
+        })
+    );
+}
+
+#[test]
+fn pre_entity() {
+    for (value, kind) in [
+        (
+            json!({"type": "pre", "offset": 0, "length": 2}),
+            MessageEntityKind::Pre,
+        ),
+        (
+            json!({"type": "pre", "offset": 0, "length": 2, "language": "rust"}),
+            MessageEntityKind::PreCode("rust".to_string()),
+        ),
+    ] {
+        let entity: MessageEntity = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(entity.kind, kind);
+        assert_eq!(serde_json::to_value(&entity).unwrap(), value);
+    }
+}
+
+fn entity(offset: i64, length: i64, kind: MessageEntityKind) -> MessageEntity {
+    MessageEntity {
+        offset,
+        length,
+        kind,
+    }
+}
+
+#[test]
+fn entity_range() {
+    let text = "🦀/start x";
+    let command = entity(2, 6, MessageEntityKind::BotCommand);
+    assert_eq!(command.range(text), Some(4..10));
+    assert_eq!(command.text(text), Some("/start"));
+    assert_eq!(entity(1, 2, MessageEntityKind::Bold).range(text), None);
+    assert_eq!(
+        entity(0, 10, MessageEntityKind::Bold).range(text),
+        Some(0..12)
+    );
+    assert_eq!(entity(0, 11, MessageEntityKind::Bold).range(text), None);
+}
+
+#[test]
+fn from_entities() {
+    let (plain, entities) = text().to_entities();
+    let text = RichText::from_entities(&plain, &entities);
+    assert_eq!(text.to_markdown_v2(), self::text().to_markdown_v2());
+    assert_eq!(text.to_entities(), (plain, entities));
+
+    // Partially overlapping entities are split at the end of the outer one.
+    let entities = vec![
+        entity(0, 4, MessageEntityKind::Bold),
+        entity(2, 4, MessageEntityKind::Italic),
+        entity(8, 20, MessageEntityKind::Code),
+    ];
+    let text = RichText::from_entities("ab😀cd ef", &entities);
+    assert_eq!(text.to_html(), "<b>ab<i>😀</i></b><i>cd</i> ef");
+    assert_eq!(text.to_markdown_v2(), "*ab_😀_*_cd_ ef");
+}
+
+#[test]
+fn split() {
+    let chunks = |text: &RichText, max_length| -> Vec<String> {
+        text.split(max_length)
+            .iter()
+            .map(|chunk| chunk.to_markdown_v2())
+            .collect()
+    };
+
+    let text = RichText::from("one two\nthree\n\nfour five");
+    assert_eq!(chunks(&text, 100), vec!["one two\nthree\n\nfour five"]);
+    assert_eq!(chunks(&text, 14), vec!["one two\nthree", "four five"]);
+    assert_eq!(chunks(&text, 10), vec!["one two", "three", "four five"]);
+    assert_eq!(
+        chunks(&text, 4),
+        vec!["one", "two", "thre", "e", "four", "five"]
+    );
+    assert!(RichText::from(" \n\n ").split(10).is_empty());
+
+    // Entities are kept whole when possible, otherwise continue in the next chunk.
+    let mut text = RichText::new();
+    text.plain("a b ").bold("c d").plain(" e.");
+    assert_eq!(chunks(&text, 8), vec!["a b *c d*", r"e\."]);
+    assert_eq!(chunks(&text, 5), vec!["a b", "*c d*", r"e\."]);
+    assert_eq!(chunks(&text, 2), vec!["a", "b", "*c *", "*d*", r"e\."]);
+
+    let text = RichText::from("😀😀😀");
+    assert_eq!(chunks(&text, 5), vec!["😀😀", "😀"]);
+    assert_eq!(chunks(&text, 1), vec!["😀", "😀", "😀"]);
+}
+
+#[test]
+fn split_code() {
+    // Whitespace inside split code blocks is kept.
+    let code = "fn main() {\n    println!(\"Hello!\");\n}\n";
+    let mut text = RichText::new();
+    text.pre_with_language(code, "rust");
+    let chunks = text.split(16);
+    assert!(chunks.len() > 1);
+    let joined: String = chunks.iter().map(|chunk| chunk.to_plain()).collect();
+    assert_eq!(joined, code);
+    for chunk in &chunks {
+        let (_, entities) = chunk.to_entities();
+        assert_eq!(entities.len(), 1);
+        assert_eq!(
+            entities[0].kind,
+            MessageEntityKind::PreCode("rust".to_string())
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use telegram_bot_raw::{
+    AllowedUpdate, Body, DeleteWebhook, GetWebhookInfo, HttpResponse, MultipartValue, Request,
+    ResponseType, SetWebhook,
+};
+
+fn response(body: &str) -> HttpResponse {
+    HttpResponse {
+        status: 200,
+        headers: Vec::new(),
+        body: Some(body.as_bytes().to_vec()),
+    }
+}
+
+#[test]
+fn webhook_info() {
+    let info = <GetWebhookInfo as Request>::Response::deserialize(response(
+        r#"{"ok":true,"result":{
+            "url":"https://example.com/telegram",
+            "has_custom_certificate":false,
+            "pending_update_count":3,
+            "max_connections":40,
+            "ip_address":"1.2.3.4",
+            "allowed_updates":["message","chosen_inline_result","my_chat_member"]
+        }}"#,
+    ))
+    .unwrap();
+    assert_eq!(info.url, "https://example.com/telegram");
+    assert_eq!(info.pending_update_count, 3);
+    assert_eq!(
+        info.allowed_updates,
+        Some(vec![
+            AllowedUpdate::Message,
+            AllowedUpdate::ChosenInlineResult,
+            AllowedUpdate::Unknown,
+        ])
+    );
+
+    let info = <GetWebhookInfo as Request>::Response::deserialize(response(
+        r#"{"ok":true,"result":{"url":"","has_custom_certificate":false,"pending_update_count":0}}"#,
+    ))
+    .unwrap();
+    assert_eq!(info.url, "");
+    assert_eq!(info.allowed_updates, None);
+}
+
+#[test]
+fn set_webhook() {
+    let mut request = SetWebhook::new("https://example.com/telegram");
+    request
+        .allowed_updates(&[AllowedUpdate::Message, AllowedUpdate::ChosenInlineResult])
+        .secret_token("secret")
+        .drop_pending_updates();
+    let multipart = match request.serialize().unwrap().body {
+        Body::Multipart(multipart) => multipart,
+        body => panic!("unexpected body: {}", body),
+    };
+    let field = |name| {
+        multipart.iter().find_map(|(key, value)| match value {
+            MultipartValue::Text(text) if *key == name => Some(text.as_str().to_string()),
+            _ => None,
+        })
+    };
+    assert_eq!(
+        field("allowed_updates").as_deref(),
+        Some(r#"["message","chosen_inline_result"]"#)
+    );
+    assert_eq!(field("secret_token").as_deref(), Some("secret"));
+    assert_eq!(field("drop_pending_updates").as_deref(), Some("true"));
+
+    let result = <SetWebhook as Request>::Response::deserialize(response(
+        r#"{"ok":true,"result":true,"description":"Webhook was set"}"#,
+    ));
+    assert!(result.is_ok());
+}
+
+#[test]
+fn delete_webhook() {
+    let mut request = DeleteWebhook::new();
+    request.drop_pending_updates();
+    assert_eq!(
+        request.serialize().unwrap().body,
+        Body::Json(r#"{"drop_pending_updates":true}"#.to_string())
+    );
+
+    let result = <DeleteWebhook as Request>::Response::deserialize(response(
+        r#"{"ok":true,"result":true,"description":"Webhook was deleted"}"#,
+    ));
+    assert!(result.is_ok());
+
+    let result = <DeleteWebhook as Request>::Response::deserialize(response(
+        r#"{"ok":false,"error_code":401,"description":"Unauthorized"}"#,
+    ));
+    assert!(result.is_err());
+}